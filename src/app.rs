@@ -4,38 +4,169 @@ use ratatui::{
     DefaultTerminal,
 };
 
+use crate::config::Config;
+use crate::filter::Filter;
+use crate::keymap::{Action, KeyMap, Mode};
+use crate::pomodoro::{Phase, Pomodoro, PomodoroSettings};
+use crate::project;
+use crate::quickadd::QuickAdd;
+use crate::theme::Theme;
+use crate::todo::{Task, Workflow};
 use crate::Todo;
+use chrono::{DateTime, Local, Utc};
 use color_eyre::Result;
+use std::collections::HashSet;
+use std::time::Duration;
+
+///Value asked to the user in a popup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    NewTask,
+    Tag,
+    Dependencies,
+    Filter,
+    SaveView,
+}
+
+impl PromptKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PromptKind::NewTask => "New task (!priority #tag @assignee due:date every:week)",
+            PromptKind::Tag => "Add tag",
+            PromptKind::Dependencies => "Depends on (task ids)",
+            PromptKind::Filter => "Filter (query or view name, empty to clear)",
+            PromptKind::SaveView => "Save the filter as",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
+///Action waiting for a yes/no answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Remove(usize),
+    EmptyTrash,
+}
+
+impl Confirmation {
+    pub fn question(&self) -> String {
+        match self {
+            Confirmation::Remove(1) => "Remove the task?".to_string(),
+            Confirmation::Remove(count) => format!("Remove {} tasks?", count),
+            Confirmation::EmptyTrash => "Empty the trash? Tasks can't be restored after this".to_string(),
+        }
+    }
+}
+
+///View displayed in the main area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    List,
+    Trash,
+    Stats,
+    Board,
+}
 
 #[derive(Debug)]
 pub struct App {
     list: Todo,
+    ///Name of the list file shown in the header
+    list_name: String,
+    ///Names of the files of an aggregated list, shown above their tasks
+    source_names: Vec<String>,
     exit: bool,
     state: ListState,
     edit: bool,
     edit_name: String,
     edit_priority: u8,
+    keymap: KeyMap,
+    theme: Theme,
+    marked: HashSet<u64>,
+    mark_anchor: Option<u64>,
+    prompt: Option<Prompt>,
+    confirm_delete: bool,
+    confirmation: Option<Confirmation>,
+    screen: Screen,
+    trash_state: ListState,
+    workflow: Workflow,
+    board_column: usize,
+    board_row: usize,
+    ready_only: bool,
+    ///Current user, from the config or $USER
+    user: Option<String>,
+    mine_only: bool,
+    group_by_assignee: bool,
+    message: Option<String>,
+    ///Set when saving failed on quit
+    save_failed: bool,
+    pomodoro_settings: PomodoroSettings,
+    pomodoro: Option<Pomodoro>,
+    filter: Option<Filter>,
 }
 
 impl App {
+    ///Time between two redraws when no key is pressed, keeps the timers live
+    const TICK: Duration = Duration::from_secs(1);
+
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         while !self.exit {
             terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
-            if let Event::Key(key) = event::read()? {
-                self.handle_key(key);
-            };
+            if event::poll(Self::TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    self.handle_key(key);
+                };
+            }
+            self.tick(Utc::now());
         }
         Ok(())
     }
 
     pub fn new(todo: Todo) -> Self {
+        Self::with_config(todo, Config::default())
+    }
+
+    pub fn with_config(todo: Todo, config: Config) -> Self {
+        let user = config.current_user();
+        let list_name = match todo.sources().len() {
+            0 => project::label(&Todo::load_path()),
+            count => format!("{} lists", count),
+        };
+        let source_names = todo.sources().into_iter().map(project::label).collect();
         App {
             list: todo,
+            list_name,
+            source_names,
             exit: false,
             state: ListState::default(),
             edit: false,
             edit_name: String::new(),
             edit_priority: 0,
+            keymap: config.keymap,
+            theme: config.theme,
+            marked: HashSet::new(),
+            mark_anchor: None,
+            prompt: None,
+            confirm_delete: config.confirm_delete,
+            confirmation: None,
+            screen: Screen::List,
+            trash_state: ListState::default(),
+            workflow: config.workflow,
+            board_column: 0,
+            board_row: 0,
+            ready_only: false,
+            user,
+            mine_only: false,
+            group_by_assignee: false,
+            message: None,
+            save_failed: false,
+            pomodoro_settings: config.pomodoro,
+            pomodoro: None,
+            filter: None,
         }
     }
 
@@ -45,18 +176,106 @@ impl App {
         &self.list
     }
 
+    pub fn get_list_name(&self) -> &str{
+        &self.list_name
+    }
+
     pub fn get_state(&mut self) -> &mut ListState{
         &mut self.state
     }
 
+    ///Index in the list of the selected task
     pub fn get_selected(&self) -> Option<usize>{
-        self.state.selected()
+        let row = self.state.selected()?;
+        self.visible_indexes().get(row).copied()
+    }
+
+    ///Indexes of the tasks shown in the list view, in display order
+    pub fn visible_indexes(&self) -> Vec<usize>{
+        let today = Local::now().date_naive();
+        let mut indexes: Vec<usize> = (0..self.list.items().len())
+            .filter(|i| !self.ready_only || self.list.is_ready(*i))
+            .filter(|i| !self.mine_only || self.is_mine(self.list.task(*i)))
+            .filter(|i| self.filter.as_ref().is_none_or(|filter| filter.matches(self.list.task(*i), &self.list, today)))
+            .collect();
+        if self.is_grouped() {
+            //The order of the list is kept in each group
+            indexes.sort_by_key(|i| self.group_key(self.list.task(*i)));
+        }
+        indexes
+    }
+
+    ///Tasks are shown in groups: by file in an aggregated list, by assignee when asked
+    pub fn is_grouped(&self) -> bool{
+        self.group_by_assignee || !self.source_names.is_empty()
+    }
+
+    ///Order of the group of a task, by file then by assignee with unassigned tasks last
+    pub fn group_key(&self, task:&Task) -> (usize, bool, Option<String>){
+        let source = self.list.source_index(task);
+        match &task.assignee {
+            Some(assignee) if self.group_by_assignee => (source, false, Some(assignee.to_lowercase())),
+            _ => (source, self.group_by_assignee, None),
+        }
+    }
+
+    ///Title of the group of a task
+    pub fn group_label(&self, task:&Task) -> String{
+        let mut parts = vec![];
+        if let Some(name) = self.source_names.get(self.list.source_index(task)) {
+            parts.push(name.as_str());
+        }
+        if self.group_by_assignee {
+            parts.push(task.assignee.as_deref().unwrap_or("Unassigned"));
+        }
+        parts.join(" - ")
+    }
+
+    ///The task is assigned to the current user
+    pub fn is_mine(&self, task:&Task) -> bool{
+        self.user.as_deref().is_some_and(|user| task.is_assigned_to(user))
+    }
+
+    pub fn get_user(&self) -> Option<&str>{
+        self.user.as_deref()
+    }
+
+    pub fn is_mine_only(&self) -> bool{
+        self.mine_only
+    }
+
+    pub fn is_grouped_by_assignee(&self) -> bool{
+        self.group_by_assignee
+    }
+
+    pub fn get_filter(&self) -> Option<&Filter>{
+        self.filter.as_ref()
+    }
+
+    pub fn is_ready_only(&self) -> bool{
+        self.ready_only
+    }
+
+    pub fn get_pomodoro(&self) -> Option<&Pomodoro>{
+        self.pomodoro.as_ref()
+    }
+
+    pub fn get_message(&self) -> Option<&String>{
+        self.message.as_ref()
     }
 
     pub fn is_edit_mode(&self) -> bool{
         self.edit
     }
 
+    ///Task that will be added by the new task prompt
+    pub fn get_new_task_preview(&self) -> Option<QuickAdd>{
+        self.prompt
+            .as_ref()
+            .filter(|prompt| prompt.kind == PromptKind::NewTask)
+            .map(|prompt| QuickAdd::parse(&prompt.text, Local::now().date_naive()))
+    }
+
     pub fn get_edit_name(&self) -> &String{
         &self.edit_name
     }
@@ -65,53 +284,239 @@ impl App {
         self.edit_priority
     }
 
+    pub fn get_keymap(&self) -> &KeyMap{
+        &self.keymap
+    }
+
+    pub fn get_theme(&self) -> &Theme{
+        &self.theme
+    }
+
+    pub fn get_mode(&self) -> Mode{
+        if self.confirmation.is_some() {
+            Mode::Confirm
+        } else if self.prompt.is_some() {
+            Mode::Input
+        } else if self.edit {
+            Mode::Edit
+        } else {
+            match self.screen {
+                Screen::List => Mode::Normal,
+                Screen::Trash => Mode::Trash,
+                Screen::Stats => Mode::Stats,
+                Screen::Board => Mode::Board,
+            }
+        }
+    }
+
+    pub fn get_screen(&self) -> Screen{
+        self.screen
+    }
+
+    pub fn get_confirmation(&self) -> Option<Confirmation>{
+        self.confirmation
+    }
+
+    pub fn get_trash_state(&mut self) -> &mut ListState{
+        &mut self.trash_state
+    }
+
+    pub fn get_workflow(&self) -> &Workflow{
+        &self.workflow
+    }
+
+    ///Indexes of the tasks in each column of the board
+    pub fn board_columns(&self) -> Vec<Vec<usize>>{
+        let mut columns = vec![vec![]; self.workflow.states().len()];
+        for (i, task) in self.list.items().iter().enumerate() {
+            columns[self.workflow.state_of(task)].push(i);
+        }
+        columns
+    }
+
+    ///Selected column and row of the board
+    pub fn get_board_selection(&self) -> (usize, usize){
+        (self.board_column, self.board_row)
+    }
+
+    pub fn is_marked(&self, id: u64) -> bool{
+        self.marked.contains(&id)
+    }
+
+    pub fn marked_count(&self) -> usize{
+        self.marked.len()
+    }
+
+    pub fn get_prompt(&self) -> Option<&Prompt>{
+        self.prompt.as_ref()
+    }
+
     //----Event handling
 
     fn handle_key(&mut self, key: KeyEvent) {
         if key.kind != KeyEventKind::Press {
             return;
         }
+        self.message = None;
+        let action = self.keymap.action(self.get_mode(), &key);
+        if let Some(confirmation) = self.confirmation {
+            match action {
+                Some(Action::Confirm) => {
+                    self.confirmation = None;
+                    match confirmation {
+                        Confirmation::Remove(_) => self.remove_task(),
+                        Confirmation::EmptyTrash => self.list.empty_trash(),
+                    }
+                }
+                Some(Action::Cancel) => self.confirmation = None,
+                _ => {}
+            }
+            return;
+        }
+        if let Some(prompt) = &mut self.prompt {
+            match action {
+                Some(Action::EraseText) => {
+                    prompt.text.pop();
+                }
+                Some(Action::SaveEdit) => self.close_prompt(true),
+                Some(Action::CancelEdit) => self.close_prompt(false),
+                _ => {
+                    if let KeyCode::Char(c) = key.code {
+                        prompt.text.push(c);
+                    }
+                }
+            }
+            return;
+        }
         if self.edit {
-            match key.code {
-                KeyCode::Char('+') => self.change_priority(true),
-                KeyCode::Char('-') => self.change_priority(false),
-                KeyCode::Char(c) => self.add_text(c),
-                KeyCode::Backspace => self.erase_text(),
-                KeyCode::Enter => self.toggle_edit_mode(false),
-                KeyCode::Esc => self.toggle_edit_mode(true),
+            match action {
+                Some(Action::IncreasePriority) => self.change_priority(true),
+                Some(Action::DecreasePriority) => self.change_priority(false),
+                Some(Action::EraseText) => self.erase_text(),
+                Some(Action::SaveEdit) => self.toggle_edit_mode(false),
+                Some(Action::CancelEdit) => self.toggle_edit_mode(true),
+                _ => {
+                    if let KeyCode::Char(c) = key.code {
+                        self.add_text(c);
+                    }
+                }
+            }
+            return;
+        }
+        match action {
+            Some(Action::Quit) => return self.quit(),
+            Some(Action::ToggleTrash) => return self.toggle_screen(Screen::Trash),
+            Some(Action::ToggleStats) => return self.toggle_screen(Screen::Stats),
+            Some(Action::ToggleBoard) => return self.toggle_screen(Screen::Board),
+            _ => {}
+        }
+        if self.screen == Screen::Trash {
+            match action {
+                Some(Action::SelectNone) => self.trash_state.select(None),
+                Some(Action::SelectNext) => self.trash_state.select_next(),
+                Some(Action::SelectPrevious) => self.trash_state.select_previous(),
+                Some(Action::SelectFirst) => self.trash_state.select_first(),
+                Some(Action::SelectLast) => self.trash_state.select_last(),
+                Some(Action::RestoreTask) => self.restore_task(),
+                Some(Action::EmptyTrash) if !self.list.trash().is_empty() => {
+                    self.confirmation = Some(Confirmation::EmptyTrash);
+                }
                 _ => {}
             }
             return;
         }
-        match key.code {
-            KeyCode::Char('q') | KeyCode::Esc => {
-                let _ = self.list.save();
-                self.exit = true
+        if self.screen == Screen::Board {
+            match action {
+                Some(Action::SelectNext) => self.board_select(self.board_column, self.board_row + 1),
+                Some(Action::SelectPrevious) => {
+                    self.board_select(self.board_column, self.board_row.saturating_sub(1))
+                }
+                Some(Action::ColumnLeft) => self.board_select(self.board_column.saturating_sub(1), self.board_row),
+                Some(Action::ColumnRight) => self.board_select(self.board_column + 1, self.board_row),
+                Some(Action::MoveCardLeft) => self.move_card(false),
+                Some(Action::MoveCardRight) => self.move_card(true),
+                _ => {}
             }
-            KeyCode::Char('h') | KeyCode::Left => self.select_none(),
-            KeyCode::Char('j') | KeyCode::Down => self.select_next(),
-            KeyCode::Char('k') | KeyCode::Up => self.select_previous(),
-            KeyCode::Char('g') | KeyCode::Home => self.select_first(),
-            KeyCode::Char('G') | KeyCode::End => self.select_last(),
-            KeyCode::Char('a') => self.add_task(),
-            KeyCode::Delete => self.remove_task(),
-            KeyCode::Enter => self.toggle_edit_mode(false),
-            KeyCode::Char('l') | KeyCode::Right => {
-                self.toggle_status();
+            return;
+        }
+        if self.screen != Screen::List {
+            return;
+        }
+        match action {
+            Some(Action::SelectNone) => self.select_none(),
+            Some(Action::SelectNext) => self.select_next(),
+            Some(Action::SelectPrevious) => self.select_previous(),
+            Some(Action::SelectFirst) => self.select_first(),
+            Some(Action::SelectLast) => self.select_last(),
+            Some(Action::AddTask) => self.add_task(),
+            Some(Action::RemoveTask) => self.ask_remove_task(),
+            Some(Action::Edit) => self.toggle_edit_mode(false),
+            Some(Action::ToggleDone) => self.toggle_status(),
+            Some(Action::CycleSort) => self.cycle_sort(),
+            Some(Action::MoveUp) => self.move_task(true),
+            Some(Action::MoveDown) => self.move_task(false),
+            Some(Action::ToggleMark) => self.toggle_mark(),
+            Some(Action::MarkRange) => self.mark_range(),
+            Some(Action::ClearMarks) => self.clear_marks(),
+            Some(Action::PriorityUp) => self.shift_priority(true),
+            Some(Action::PriorityDown) => self.shift_priority(false),
+            Some(Action::AddTag) => self.open_prompt(PromptKind::Tag),
+            Some(Action::SetDependencies) => self.open_prompt(PromptKind::Dependencies),
+            Some(Action::ToggleTimer) => self.toggle_timer(),
+            Some(Action::Filter) => self.open_prompt(PromptKind::Filter),
+            Some(Action::SaveView) => self.open_prompt(PromptKind::SaveView),
+            Some(Action::TogglePomodoro) => self.toggle_pomodoro(Utc::now()),
+            Some(Action::ToggleReady) => self.keep_selection(|app| app.ready_only = !app.ready_only),
+            Some(Action::ToggleMine) => {
+                if self.user.is_none() {
+                    self.message = Some("No current user, set \"user\" in the config or $USER".to_string());
+                } else {
+                    self.keep_selection(|app| app.mine_only = !app.mine_only);
+                }
             }
+            Some(Action::GroupByAssignee) => self.keep_selection(|app| app.group_by_assignee = !app.group_by_assignee),
             _ => {}
         }
     }
 
+    //A failed save is reported instead of leaving, quitting again leaves without saving
+    fn quit(&mut self) {
+        if let Err(e) = self.list.save() {
+            if !self.save_failed {
+                self.save_failed = true;
+                self.message = Some(format!("Could not save the list : {}. Quit again to leave without saving", e));
+                return;
+            }
+        }
+        self.exit = true
+    }
+
+    //Opens a screen, or goes back to the list if it is already open
+    fn toggle_screen(&mut self, screen: Screen) {
+        self.screen = if self.screen == screen { Screen::List } else { screen };
+    }
+
     fn select_none(&mut self) {
         self.state.select(None);
     }
 
     fn select_next(&mut self) {
-        self.state.select_next();
+        let len = self.visible_indexes().len();
+        if len == 0 {
+            return;
+        }
+        let row = self.state.selected().map_or(0, |row| row.saturating_add(1).min(len - 1));
+        self.state.select(Some(row));
     }
+
+    //Without a selection, the last row is selected
     fn select_previous(&mut self) {
-        self.state.select_previous();
+        let len = self.visible_indexes().len();
+        if len == 0 {
+            return;
+        }
+        let row = self.state.selected().map_or(len - 1, |row| row.min(len - 1).saturating_sub(1));
+        self.state.select(Some(row));
     }
 
     fn select_first(&mut self) {
@@ -119,33 +524,266 @@ impl App {
     }
 
     fn select_last(&mut self) {
-        if let Some(index) = self.list.items().len().checked_sub(1) {
-            self.state.select(Some(index));
+        if let Some(row) = self.visible_indexes().len().checked_sub(1) {
+            self.state.select(Some(row));
+        }
+    }
+
+    //Selects the row showing the task at this index of the list
+    fn select_index(&mut self, index: usize) {
+        if let Some(row) = self.visible_indexes().iter().position(|i| *i == index) {
+            self.state.select(Some(row));
+        }
+    }
+
+    //Keeps a task selected after the list was sorted
+    fn select_id(&mut self, id: u64) {
+        if let Some(index) = self.list.position(id) {
+            self.select_index(index);
+        }
+    }
+
+    //Keeps the selection inside the list after tasks were hidden or removed
+    fn clamp_selection(&mut self) {
+        let len = self.visible_indexes().len();
+        if len == 0 {
+            self.select_none();
+        } else if let Some(row) = self.state.selected() {
+            self.state.select(Some(row.min(len - 1)));
+        }
+    }
+
+    fn selected_id(&self) -> Option<u64> {
+        self.get_selected().map(|i| self.list.task(i).id)
+    }
+
+    //Ids of the tasks an action applies to: the marked tasks, or else the selected one
+    fn target_ids(&self) -> Vec<u64> {
+        if self.marked.is_empty() {
+            self.selected_id().into_iter().collect()
+        } else {
+            self.list
+                .items()
+                .iter()
+                .filter(|task| self.marked.contains(&task.id))
+                .map(|task| task.id)
+                .collect()
+        }
+    }
+
+    fn target_indexes(&self) -> Vec<usize> {
+        self.target_ids()
+            .into_iter()
+            .filter_map(|id| self.list.position(id))
+            .collect()
+    }
+
+    //----Marks
+
+    fn toggle_mark(&mut self) {
+        if let Some(id) = self.selected_id() {
+            if !self.marked.remove(&id) {
+                self.marked.insert(id);
+            }
+            self.mark_anchor = Some(id);
+            self.select_next();
+        }
+    }
+
+    //Marks every task between the last marked task and the selection
+    fn mark_range(&mut self) {
+        let visible = self.visible_indexes();
+        let Some(selected) = self.state.selected().filter(|row| *row < visible.len()) else {
+            return;
+        };
+        let anchor = self
+            .mark_anchor
+            .and_then(|id| self.list.position(id))
+            .and_then(|index| visible.iter().position(|i| *i == index))
+            .unwrap_or(selected);
+        for i in &visible[anchor.min(selected)..=anchor.max(selected)] {
+            self.marked.insert(self.list.task(*i).id);
+        }
+        self.mark_anchor = self.selected_id();
+    }
+
+    fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.mark_anchor = None;
+    }
+
+    //----Prompt
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        let needs_target = matches!(kind, PromptKind::Tag | PromptKind::Dependencies);
+        if (needs_target && self.target_ids().is_empty()) || (kind == PromptKind::SaveView && self.filter.is_none()) {
+            return;
+        }
+        let text = match kind {
+            PromptKind::NewTask | PromptKind::Tag | PromptKind::SaveView => String::new(),
+            PromptKind::Filter => self.filter.as_ref().map_or(String::new(), |filter| filter.query().to_string()),
+            //Dependencies are edited one task at a time
+            PromptKind::Dependencies => match self.get_selected() {
+                Some(i) => self.list.task(i).depends_on.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" "),
+                None => return,
+            },
+        };
+        self.prompt = Some(Prompt { kind, text });
+    }
+
+    fn close_prompt(&mut self, confirm: bool) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        if !confirm {
+            return;
+        }
+        match prompt.kind {
+            PromptKind::NewTask => {
+                let parsed = QuickAdd::parse(&prompt.text, Local::now().date_naive());
+                if parsed.name.is_empty() {
+                    self.message = Some("The task needs a name".to_string());
+                    return;
+                }
+                let mut task = parsed.to_task(0);
+                //In an aggregated list, the task goes to the file of the selected task
+                task.source = self.get_selected().and_then(|i| self.list.task(i).source.as_ref()).map(|(path, _)| (path.clone(), 0));
+                let id = self.list.insert(task);
+                self.clamp_selection();
+                self.select_id(id);
+            }
+            PromptKind::Filter => {
+                let query = prompt.text.trim();
+                let query = self.list.views().get(query).map_or(query, |view| view.as_str());
+                if query.is_empty() {
+                    self.set_filter(None);
+                    return;
+                }
+                match Filter::parse(query) {
+                    Ok(filter) => self.set_filter(Some(filter.with_user(self.user.as_deref()))),
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            }
+            PromptKind::SaveView => {
+                let name = prompt.text.trim();
+                if let (Some(filter), false) = (&self.filter, name.is_empty()) {
+                    self.list.save_view(name, filter.query());
+                    self.message = Some(format!("View \"{}\" saved", name));
+                }
+            }
+            PromptKind::Tag => {
+                for i in self.target_indexes() {
+                    self.list.add_tag(i, &prompt.text);
+                }
+            }
+            PromptKind::Dependencies => {
+                let Some(i) = self.get_selected() else {
+                    return;
+                };
+                let ids: Result<Vec<u64>, _> = prompt
+                    .text
+                    .split([' ', ','])
+                    .filter(|id| !id.is_empty())
+                    .map(|id| id.trim_start_matches('#').parse::<u64>())
+                    .collect();
+                let result = match ids {
+                    Ok(ids) => self.list.set_dependencies(i, &ids).map_err(|e| e.to_string()),
+                    Err(e) => Err(format!("Invalid task id: {}", e)),
+                };
+                if let Err(e) = result {
+                    self.message = Some(e);
+                }
+                self.clamp_selection();
+            }
+        }
+    }
+
+    //Changes the filter, keeping the selected task if it is still visible
+    fn set_filter(&mut self, filter: Option<Filter>) {
+        self.keep_selection(|app| app.filter = filter);
+    }
+
+    //Changes which tasks are shown, keeping the selected task if it is still visible
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let id = self.selected_id();
+        change(self);
+        self.clamp_selection();
+        if let Some(id) = id {
+            self.select_id(id);
         }
     }
 
     //----Actions
 
     fn add_task(&mut self) {
-        self.list.add(&"New".to_string(), 0);
-        self.select_last();
-        self.toggle_edit_mode(false);
-        self.edit_name.clear();
+        self.open_prompt(PromptKind::NewTask);
+    }
+
+    fn ask_remove_task(&mut self) {
+        let count = self.target_ids().len();
+        if count == 0 {
+            return;
+        }
+        if self.confirm_delete {
+            self.confirmation = Some(Confirmation::Remove(count));
+        } else {
+            self.remove_task();
+        }
     }
 
     fn remove_task(&mut self) {
-        if let Some(i) = self.state.selected() {
-            let _ = self.list.remove(&vec![i]);
-            if self.list.items().len()==0 {
-                self.select_none();
-            }
+        let indexes = self.target_indexes();
+        if indexes.is_empty() {
+            return;
         }
+        let _ = self.list.remove(&indexes);
+        self.clear_marks();
+        self.clamp_selection();
+    }
+
+    //----Board
+
+    //Selects a card, staying inside the board
+    fn board_select(&mut self, column: usize, row: usize) {
+        let columns = self.board_columns();
+        self.board_column = column.min(columns.len() - 1);
+        let len = columns[self.board_column].len();
+        self.board_row = row.min(len.saturating_sub(1));
+    }
+
+    fn move_card(&mut self, right: bool) {
+        let columns = self.board_columns();
+        let Some(&index) = columns[self.board_column].get(self.board_row) else {
+            return;
+        };
+        let target = if right { self.board_column + 1 } else { self.board_column.wrapping_sub(1) };
+        if target >= columns.len() {
+            return;
+        }
+        let id = self.list.task(index).id;
+        self.list.set_state(index, &self.workflow, target);
+        let row = self.board_columns()[target]
+            .iter()
+            .position(|i| self.list.task(*i).id == id)
+            .unwrap_or(0);
+        self.board_select(target, row);
+    }
+
+    fn restore_task(&mut self) {
+        let Some(i) = self.trash_state.selected() else {
+            return;
+        };
+        if let Ok(index) = self.list.restore(i) {
+            self.select_index(index);
+        }
+        let len = self.list.trash().len();
+        self.trash_state.select(if len == 0 { None } else { Some(i.min(len - 1)) });
     }
 
     fn toggle_edit_mode(&mut self, cancel:bool) {
         let current_task;
         let current_task_index: usize;
-        match self.state.selected() {
+        match self.get_selected() {
             Some(i) => {
                 current_task = self.list.task(i);
                 current_task_index = i;
@@ -164,21 +802,112 @@ impl App {
             self.edit_name = current_task.name.clone();
             self.edit_priority = current_task.priority;
         } else {
-            //edit finished
-            self.list
-                .rename(current_task_index, &self.edit_name.clone());
-            self.list
-                .set_priority(current_task_index, self.edit_priority);
+            //edit finished, the name is kept as typed, the inline syntax is only read when adding tasks
+            let id = current_task.id;
+            let name = self.edit_name.clone();
+            let priority = self.edit_priority;
+            self.list.update(current_task_index, |task| {
+                task.name = name;
+                task.priority = priority;
+            });
+            self.select_id(id);
+        }
+    }
+
+    ///Ends the pomodoro cycles that are over, logging the finished work cycles
+    fn tick(&mut self, now: DateTime<Utc>) {
+        let Some(pomodoro) = &mut self.pomodoro else {
+            return;
+        };
+        let Some(finished) = pomodoro.tick(now) else {
+            return;
+        };
+        let Some(index) = self.list.position(pomodoro.task_id()) else {
+            self.pomodoro = None;
+            return;
+        };
+        let name = &self.list.task(index).name;
+        let message = match finished {
+            Phase::Work => format!("Pomodoro done on \"{}\", take a break", name),
+            Phase::Break => format!("Break over, back to \"{}\"", name),
+        };
+        pomodoro.settings().notify(&message);
+        if finished == Phase::Work {
+            self.list.log_pomodoro(index, now);
+        }
+        self.message = Some(message);
+    }
+
+    fn toggle_pomodoro(&mut self, now: DateTime<Utc>) {
+        if self.pomodoro.take().is_some() {
+            return;
+        }
+        if let Some(id) = self.selected_id() {
+            self.pomodoro = Some(Pomodoro::new(id, self.pomodoro_settings.clone(), now));
         }
     }
 
+    fn toggle_timer(&mut self) {
+        let Some(index) = self.get_selected() else {
+            return;
+        };
+        if self.list.running_timer() == Some(index) {
+            self.list.stop_timer();
+        } else {
+            self.list.start_timer(index);
+        }
+    }
+
+    //Marks the targets as done, or as not done if they all are done already
     fn toggle_status(&mut self) {
-        if let Some(i) = self.get_selected() {
-            self.list.done(i);
+        let ids = self.target_ids();
+        let done = !ids
+            .iter()
+            .filter_map(|id| self.list.position(*id))
+            .all(|i| self.list.task(i).done);
+        let selected = self.selected_id();
+        for id in ids {
+            if let Some(i) = self.list.position(id) {
+                self.list.set_done(i, done);
+            }
+        }
+        if let Some(id) = selected {
+            self.select_id(id);
+        }
+    }
+
+    fn shift_priority(&mut self, increment: bool) {
+        let selected = self.selected_id();
+        for id in self.target_ids() {
+            if let Some(i) = self.list.position(id) {
+                let priority = self.list.task(i).priority;
+                let priority = if increment { (priority + 1).min(10) } else { priority.saturating_sub(1) };
+                self.list.set_priority(i, priority);
+            }
+        }
+        if let Some(id) = selected {
+            self.select_id(id);
+        }
+    }
+
+    fn cycle_sort(&mut self) {
+        let id = self.selected_id();
+        self.list.set_sort_mode(self.list.sort_mode().next());
+        if let Some(id) = id {
+            self.select_id(id);
+        }
+    }
+
+    fn move_task(&mut self, up: bool) {
+        let selected = self.selected_id();
+        if self.list.move_tasks(&self.target_indexes(), up) {
+            if let Some(id) = selected {
+                self.select_id(id);
+            }
         }
     }
 
-    fn add_text(&mut self, text: char) -> () {
+    fn add_text(&mut self, text: char) {
         self.edit_name.push(text);
     }
 
@@ -186,12 +915,12 @@ impl App {
         self.edit_name.pop();
     }
 
-    fn change_priority(&mut self, increment: bool) -> () {
+    fn change_priority(&mut self, increment: bool) {
         if increment {
             if self.edit_priority==10 {return;}
             self.edit_priority += 1;
         } else {
-            if self.edit_priority<=0 {return;}
+            if self.edit_priority==0 {return;}
             self.edit_priority -= 1;
         }
     }
@@ -205,9 +934,9 @@ mod tests {
     #[test]
     fn select_next_test() {
         let mut app = App::new(Todo::new());
-        app.list.add(&"Task1".to_string(), 2);
-        app.list.add(&"Task2".to_string(), 2);
-        app.list.add(&"Task3".to_string(), 2);
+        app.list.add("Task1", 2);
+        app.list.add("Task2", 2);
+        app.list.add("Task3", 2);
 
         app.get_state().select(None);
         assert_eq!(app.get_selected(), None);
@@ -225,10 +954,112 @@ mod tests {
         assert_eq!(app.get_selected(), None);
     }
 
+    #[test]
+    fn select_previous_then_next_without_selection(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 2);
+        app.list.add("Task2", 2);
+
+        app.select_none();
+        app.select_previous();
+        assert_eq!(app.get_state().selected(), Some(1));
+        app.select_next();
+        assert_eq!(app.get_state().selected(), Some(1));
+        //A row left past the end after tasks were removed
+        app.get_state().select(Some(7));
+        app.select_previous();
+        assert_eq!(app.get_state().selected(), Some(0));
+    }
+
+    #[test]
+    fn toggle_timer_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 2);
+        app.list.add("Task2", 2);
+
+        app.select_first();
+        app.toggle_timer();
+        assert_eq!(app.get_list().running_timer(), Some(0));
+        app.select_next();
+        app.toggle_timer();
+        assert_eq!(app.get_list().running_timer(), Some(1));
+        app.toggle_timer();
+        assert_eq!(app.get_list().running_timer(), None);
+    }
+
+    #[test]
+    fn pomodoro_logs_finished_cycles(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 2);
+        app.select_first();
+        let start = Utc::now();
+        app.toggle_pomodoro(start);
+        app.tick(start + chrono::TimeDelta::minutes(10));
+        assert!(app.get_list().task(0).pomodoros.is_empty());
+        app.tick(start + chrono::TimeDelta::minutes(25));
+        assert_eq!(app.get_list().task(0).pomodoros.len(), 1);
+        assert_eq!(app.get_pomodoro().map(|p| p.phase()), Some(Phase::Break));
+        assert!(app.get_message().is_some());
+        app.toggle_pomodoro(start);
+        assert!(app.get_pomodoro().is_none());
+    }
+
+    #[test]
+    fn edit_keeps_the_name_as_typed(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Fix #42 for @ann", 2);
+
+        app.select_first();
+        app.toggle_edit_mode(false);
+        for c in " !8".chars() {
+            app.add_text(c);
+        }
+        app.change_priority(true);
+        app.toggle_edit_mode(false);
+        let task = app.get_list().task(0);
+        assert_eq!(task.name, "Fix #42 for @ann !8");
+        assert_eq!(task.priority, 3);
+        assert!(task.tags.is_empty());
+        assert_eq!(task.assignee, None);
+    }
+
+    #[test]
+    fn filter_prompt_and_views(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 8);
+        app.list.add("Task2", 1);
+
+        app.open_prompt(PromptKind::Filter);
+        app.prompt.as_mut().unwrap().text.push_str("priority>=5");
+        app.close_prompt(true);
+        assert_eq!(app.visible_indexes(), vec![0]);
+
+        app.open_prompt(PromptKind::SaveView);
+        app.prompt.as_mut().unwrap().text.push_str("urgent");
+        app.close_prompt(true);
+        assert_eq!(app.get_list().views().get("urgent").map(|q| q.as_str()), Some("priority>=5"));
+
+        //An empty query clears the filter, a view name applies the view
+        app.open_prompt(PromptKind::Filter);
+        app.prompt.as_mut().unwrap().text.clear();
+        app.close_prompt(true);
+        assert_eq!(app.visible_indexes().len(), 2);
+        app.open_prompt(PromptKind::Filter);
+        app.prompt.as_mut().unwrap().text.push_str("urgent");
+        app.close_prompt(true);
+        assert_eq!(app.visible_indexes(), vec![0]);
+
+        app.open_prompt(PromptKind::Filter);
+        app.prompt.as_mut().unwrap().text.push_str(" and (");
+        app.close_prompt(true);
+        assert!(app.get_message().is_some());
+        assert_eq!(app.get_filter().map(|f| f.query()), Some("priority>=5"));
+    }
+
     #[test]
     fn edit_task_test(){
         let mut app = App::new(Todo::new());
-        app.list.add(&"Task1".to_string(), 2);
+        app.list.add("Task1", 2);
 
         app.select_first();
         app.toggle_edit_mode(false);
@@ -243,7 +1074,7 @@ mod tests {
     #[test]
     fn edit_task_cancel_test(){
         let mut app = App::new(Todo::new());
-        app.list.add(&"Task1".to_string(), 2);
+        app.list.add("Task1", 2);
 
         app.select_first();
         app.toggle_edit_mode(false);
@@ -258,17 +1089,247 @@ mod tests {
     #[test]
     fn add_task_test(){
         let mut app = App::new(Todo::new());
+        app.list.add("Task1", 5);
+        app.add_task();
+        assert_eq!(app.get_prompt().map(|p| p.kind), Some(PromptKind::NewTask));
+        //Nothing is added when the prompt is cancelled
+        app.close_prompt(false);
+        assert_eq!(app.get_list().items().len(), 1);
+
         app.add_task();
-        assert_eq!(app.get_list().task(0).name,"New".to_string());
-        assert_eq!(app.is_edit_mode(),true);
+        app.prompt.as_mut().unwrap().text.push_str("Task2 !7 #api");
+        assert_eq!(app.get_new_task_preview().map(|p| p.name), Some("Task2".to_string()));
+        app.close_prompt(true);
+        assert_eq!(app.get_list().items().len(), 2);
+        //The new task is sorted and selected
+        assert_eq!(app.get_selected(), Some(0));
+        assert_eq!(app.get_list().task(0).name, "Task2");
+        assert_eq!(app.get_list().task(0).tags, vec!["api".to_string()]);
+
+        app.add_task();
+        app.close_prompt(true);
+        assert_eq!(app.get_list().items().len(), 2);
+        assert!(app.get_message().is_some());
+    }
+
+    #[test]
+    fn remapped_keys_test(){
+        let mut config = Config::default();
+        config.keymap.bind(Action::SelectNext, vec!["n".parse().unwrap()]);
+        let mut app = App::with_config(Todo::new(), config);
+        app.list.add("Task1", 2);
+        app.list.add("Task2", 2);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        assert_eq!(app.get_selected(), None);
+        app.handle_key(KeyEvent::from(KeyCode::Char('n')));
+        assert_eq!(app.get_selected(), Some(0));
+    }
+
+    #[test]
+    fn selection_follows_sorted_task_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 5);
+        app.list.add("Task2", 2);
+
+        app.select_first();
+        app.toggle_status();
+        assert_eq!(app.get_selected(), Some(1));
+        assert_eq!(app.get_list().task(1).name, "Task1".to_string());
+
+        app.select_first();
         app.toggle_edit_mode(false);
+        app.change_priority(true);
+        app.toggle_edit_mode(false);
+        assert_eq!(app.get_list().task(app.get_selected().unwrap()).name, "Task2".to_string());
+    }
+
+    #[test]
+    fn move_task_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 5);
+        app.list.add("Task2", 2);
+
         app.select_first();
+        app.move_task(false);
+        assert_eq!(app.get_selected(), Some(1));
+        assert_eq!(app.get_list().task(1).name, "Task1".to_string());
+        app.cycle_sort();
         assert_eq!(app.get_selected(), Some(0));
+    }
+
+    #[test]
+    fn bulk_operations_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 1);
+        app.list.add("Task2", 1);
+        app.list.add("Task3", 1);
+        app.list.add("Task4", 1);
+
+        app.select_first();
+        app.toggle_mark();
+        app.select_next();
+        app.mark_range();
+        assert_eq!(app.marked_count(), 3);
+
+        app.shift_priority(true);
+        app.open_prompt(PromptKind::Tag);
+        app.handle_key(KeyEvent::from(KeyCode::Char('x')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let tagged: Vec<&str> = app.get_list().items().iter()
+            .filter(|t| t.tags.contains(&"x".to_string()) && t.priority == 2)
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(tagged, vec!["Task1", "Task2", "Task3"]);
+
+        app.toggle_status();
+        assert_eq!(app.get_list().items().iter().filter(|t| t.done).count(), 3);
         app.remove_task();
-        app.remove_task();
-        app.remove_task();
+        assert_eq!(app.get_list().items().len(), 1);
+        assert_eq!(app.get_list().task(0).name, "Task4".to_string());
+        assert_eq!(app.marked_count(), 0);
+    }
+
+    #[test]
+    fn prompt_cancel_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 1);
+        app.select_first();
+        app.open_prompt(PromptKind::Tag);
+        assert_eq!(app.get_mode(), Mode::Input);
+        app.handle_key(KeyEvent::from(KeyCode::Char('x')));
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(app.get_mode(), Mode::Normal);
+        assert!(app.get_list().task(0).tags.is_empty());
+    }
+
+    #[test]
+    fn remove_confirmation_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 1);
+        app.select_first();
+
+        app.handle_key(KeyEvent::from(KeyCode::Delete));
+        assert_eq!(app.get_confirmation(), Some(Confirmation::Remove(1)));
+        app.handle_key(KeyEvent::from(KeyCode::Char('n')));
+        assert_eq!(app.get_list().items().len(), 1);
+
+        app.handle_key(KeyEvent::from(KeyCode::Delete));
+        app.handle_key(KeyEvent::from(KeyCode::Char('y')));
         assert_eq!(app.get_list().items().len(), 0);
-        assert_eq!(app.state.selected(), None);
+        assert_eq!(app.get_confirmation(), None);
     }
-}
 
+    #[test]
+    fn trash_restore_test(){
+        let config = Config { confirm_delete: false, ..Default::default() };
+        let mut app = App::with_config(Todo::new(), config);
+        app.list.add("Task1", 1);
+        app.list.add("Task2", 1);
+        app.select_first();
+        app.handle_key(KeyEvent::from(KeyCode::Delete));
+        assert_eq!(app.get_list().trash().len(), 1);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('T')));
+        assert_eq!(app.get_mode(), Mode::Trash);
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('r')));
+        assert_eq!(app.get_list().items().len(), 2);
+        assert!(app.get_list().trash().is_empty());
+        assert_eq!(app.get_trash_state().selected(), None);
+    }
+
+    #[test]
+    fn board_move_card_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Task1", 1);
+        app.list.add("Task2", 1);
+        app.toggle_screen(Screen::Board);
+        assert_eq!(app.get_mode(), Mode::Board);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('L')));
+        assert_eq!(app.get_board_selection(), (1, 0));
+        assert_eq!(app.get_list().task(1).state.as_deref(), Some("In Progress"));
+        app.move_card(true);
+        app.move_card(true);
+        assert_eq!(app.get_board_selection(), (3, 0));
+        assert!(app.get_list().task(1).done);
+        app.move_card(true);
+        assert_eq!(app.get_board_selection(), (3, 0));
+        assert_eq!(app.board_columns(), vec![vec![0], vec![], vec![], vec![1]]);
+    }
+
+    #[test]
+    fn dependencies_and_ready_view_test(){
+        let mut app = App::new(Todo::new());
+        app.list.add("Build", 1);
+        app.list.add("Release", 1);
+        app.select_last();
+        app.open_prompt(PromptKind::Dependencies);
+        app.handle_key(KeyEvent::from(KeyCode::Char('1')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.get_list().task(1).depends_on, vec![1]);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('R')));
+        assert_eq!(app.visible_indexes(), vec![0]);
+        assert_eq!(app.get_selected(), Some(0));
+
+        app.select_first();
+        app.open_prompt(PromptKind::Dependencies);
+        app.handle_key(KeyEvent::from(KeyCode::Char('2')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.get_message().is_some());
+        assert!(app.get_list().task(0).depends_on.is_empty());
+    }
+
+    #[test]
+    fn mine_and_group_by_assignee(){
+        let mut app = App::new(Todo::new());
+        app.user = Some("ann".to_string());
+        for (name, assignee) in [("Task1", Some("bob")), ("Task2", None), ("Task3", Some("Ann")), ("Task4", Some("bob"))] {
+            app.list.add(name, 1);
+            let index = app.list.items().len() - 1;
+            app.list.update(index, |task| task.assignee = assignee.map(|a| a.to_string()));
+        }
+        app.handle_key(KeyEvent::from(KeyCode::Char('A')));
+        let names: Vec<&str> = app.visible_indexes().iter().map(|i| app.get_list().task(*i).name.as_str()).collect();
+        assert_eq!(names, vec!["Task3", "Task1", "Task4", "Task2"]);
+
+        app.select_last();
+        app.handle_key(KeyEvent::from(KeyCode::Char('M')));
+        assert_eq!(app.visible_indexes().len(), 1);
+        assert_eq!(app.get_selected(), Some(2));
+        app.user = None;
+        app.handle_key(KeyEvent::from(KeyCode::Char('M')));
+        assert!(app.is_mine_only());
+        assert!(app.get_message().is_some());
+    }
+
+    #[test]
+    fn aggregated_lists_are_grouped_by_file(){
+        let a = "./test_app_aggregated_a.json".to_string();
+        let b = "./test_app_aggregated_b.json".to_string();
+        std::fs::write(&a, r#"{"list":[{"id":1,"name":"A1","priority":1,"done":false}]}"#).unwrap();
+        std::fs::write(&b, r#"{"list":[{"id":1,"name":"B1","priority":9,"done":false,"assignee":"bob"}]}"#).unwrap();
+        let list = Todo::load_all(&[a.clone(), b.clone()]);
+        std::fs::remove_file(&a).unwrap();
+        std::fs::remove_file(&b).unwrap();
+        let mut app = App::new(list.unwrap());
+        assert_eq!(app.get_list_name(), "2 lists");
+        assert!(app.is_grouped());
+        let names: Vec<&str> = app.visible_indexes().iter().map(|i| app.get_list().task(*i).name.as_str()).collect();
+        assert_eq!(names, vec!["A1", "B1"]);
+        app.handle_key(KeyEvent::from(KeyCode::Char('A')));
+        assert_eq!(app.group_label(app.get_list().task(0)), format!("{} - bob", b));
+
+        //New tasks go to the file of the selected task
+        app.select_last();
+        app.add_task();
+        app.prompt.as_mut().unwrap().text.push_str("B2");
+        app.close_prompt(true);
+        let task = app.get_selected().map(|i| app.get_list().task(i)).unwrap();
+        assert_eq!(task.name, "B2");
+        assert_eq!(task.source, Some((b, 0)));
+    }
+}
@@ -0,0 +1,272 @@
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+use clap::{Parser, Subcommand};
+use std::io::{self, IsTerminal};
+
+use crate::config::Config;
+use crate::filter::Filter;
+use crate::quickadd::{self, QuickAdd};
+use crate::reminder;
+use crate::output::{self, Format, Template};
+use crate::project;
+use crate::report::TimeReport;
+use crate::rpc;
+use crate::server;
+use crate::stats::format_duration;
+use crate::sync::{self, Synced};
+use crate::todo::{Task, Todo};
+
+///Terminal todo list, starts the interface when no command is given
+#[derive(Parser, Debug)]
+#[command(name = "todo", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    ///Opens the project lists found on disk and the global list together, grouped by list
+    #[arg(long)]
+    pub all: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    ///Lists the tasks, colors are only used on a terminal
+    Ls {
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        ///Writes each task with a template like "{id}\t{name}", replaces the format
+        #[arg(long)]
+        template: Option<String>,
+        ///Only lists the tasks matching a filter like "priority>=5 and not done and tag:backend and due<7d"
+        #[arg(long = "where", conflicts_with = "view")]
+        query: Option<String>,
+        ///Only lists the tasks matching a saved view
+        #[arg(long)]
+        view: Option<String>,
+        ///Only lists the tasks assigned to the current user ("user" in the config, or $USER)
+        #[arg(long)]
+        mine: bool,
+    },
+    ///Manages the saved filters
+    View {
+        #[command(subcommand)]
+        command: ViewCommand,
+    },
+    ///Adds a task, fields can be written inline: "Fix login bug !7 #backend @bob due:fri every:week"
+    Add {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        text: Vec<String>,
+    },
+    ///Starts the timer of a task, stopping the running one
+    Start {
+        ///Id of the task
+        id: u64,
+    },
+    ///Stops the running timer
+    Stop,
+    ///Prints the time spent per task, tag and day
+    Report,
+    ///Sets the due date of a task
+    Due {
+        ///Id of the task
+        id: u64,
+        ///Date like 2024-03-01, "fri" or "3d", or "none" to remove it
+        date: String,
+    },
+    ///Sets when to be reminded of a task
+    RemindAt {
+        ///Id of the task
+        id: u64,
+        ///Local time like "2024-03-01 14:30", or "none" to remove it
+        time: String,
+    },
+    ///Serves a JSON API on localhost to list, get, create, update, complete and delete tasks
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    ///Speaks line-delimited JSON-RPC on stdin/stdout, for editor integrations
+    Rpc,
+    ///Watches the tasks and sends a notification when one is due or must be reminded
+    Remind {
+        ///Checks once then exits
+        #[arg(long)]
+        once: bool,
+    },
+    ///Commits the list, then pulls and pushes it with the configured git remote
+    Sync,
+    ///Creates a project list in the current directory, used here and in its subdirectories
+    Init,
+    ///Prints the changes made to a task, with their date and author
+    Log {
+        ///Id of the task, removed tasks included
+        id: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ViewCommand {
+    ///Saves a filter under a name
+    Save { name: String, query: String },
+    ///Removes a saved filter
+    Rm { name: String },
+    ///Lists the saved filters
+    Ls,
+}
+
+impl Command {
+    ///Runs the command on the list, returns true if it was modified
+    pub fn run(&self, list: &mut Todo, config: &Config) -> Result<bool, String> {
+        match self {
+            Command::Ls { format, template, query, view, mine } => {
+                if !io::stdout().is_terminal() {
+                    colored::control::set_override(false);
+                }
+                let query = match view {
+                    Some(view) => Some(list.views().get(view).ok_or(format!("No view named {}", view))?),
+                    None => query.as_ref(),
+                };
+                let user = config.current_user();
+                if *mine && user.is_none() {
+                    return Err("No current user, set \"user\" in the config or $USER".to_string());
+                }
+                let filter = query.map(|query| Filter::parse(query)).transpose().map_err(|e| e.to_string())?;
+                let filter = filter.map(|filter| filter.with_user(user.as_deref()));
+                let today = Local::now().date_naive();
+                let tasks: Vec<&Task> = list
+                    .items()
+                    .iter()
+                    .filter(|task| !*mine || user.as_deref().is_some_and(|user| task.is_assigned_to(user)))
+                    .filter(|task| filter.as_ref().is_none_or(|filter| filter.matches(task, list, today)))
+                    .collect();
+                let mut out = io::stdout().lock();
+                let written = match template {
+                    Some(template) => Template::parse(template)?.write(&mut out, &tasks),
+                    None => output::write(&mut out, &tasks, *format),
+                };
+                written.map_err(|e| e.to_string())?;
+                Ok(false)
+            }
+            Command::View { command } => match command {
+                ViewCommand::Save { name, query } => {
+                    Filter::parse(query).map_err(|e| e.to_string())?;
+                    list.save_view(name, query);
+                    Ok(true)
+                }
+                ViewCommand::Rm { name } => match list.remove_view(name) {
+                    true => Ok(true),
+                    false => Err(format!("No view named {}", name)),
+                },
+                ViewCommand::Ls => {
+                    for (name, query) in list.views() {
+                        println!("{} : {}", name, query);
+                    }
+                    Ok(false)
+                }
+            },
+            Command::Add { text } => {
+                let parsed = QuickAdd::parse(&text.join(" "), Local::now().date_naive());
+                if parsed.name.is_empty() {
+                    return Err("The task needs a name".to_string());
+                }
+                let id = list.insert(parsed.to_task(0));
+                let added = format!("Added #{} \"{}\" {}", id, parsed.name, parsed.summary());
+                println!("{}", added.trim_end());
+                Ok(true)
+            }
+            Command::Start { id } => {
+                let index = find(list, *id)?;
+                list.start_timer(index);
+                println!("Timer started on \"{}\"", list.items()[index].name);
+                Ok(true)
+            }
+            Command::Stop => match list.stop_timer() {
+                Some(index) => {
+                    let task = &list.items()[index];
+                    println!(
+                        "Timer stopped on \"{}\", {} tracked",
+                        task.name,
+                        format_duration(task.tracked(Utc::now()))
+                    );
+                    Ok(true)
+                }
+                None => Err("No timer is running".to_string()),
+            },
+            Command::Report => {
+                TimeReport::compute(list, Utc::now()).print();
+                Ok(false)
+            }
+            Command::Due { id, date } => {
+                let index = find(list, *id)?;
+                let due = match date.as_str() {
+                    "none" => None,
+                    date => Some(
+                        quickadd::parse_date(date, Local::now().date_naive()).ok_or(format!("Invalid date {}", date))?,
+                    ),
+                };
+                list.set_due(index, due);
+                Ok(true)
+            }
+            Command::RemindAt { id, time } => {
+                let index = find(list, *id)?;
+                let at = match time.as_str() {
+                    "none" => None,
+                    time => {
+                        let local = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+                            .map_err(|e| format!("Invalid time {} : {}", time, e))?;
+                        let at = Local.from_local_datetime(&local).earliest().ok_or(format!("Invalid time {}", time))?;
+                        Some(at.with_timezone(&Utc))
+                    }
+                };
+                list.set_reminder(index, at);
+                Ok(true)
+            }
+            Command::Serve { port } => {
+                server::serve(*port).map_err(|e| e.to_string())?;
+                Ok(false)
+            }
+            Command::Rpc => {
+                rpc::run().map_err(|e| e.to_string())?;
+                Ok(false)
+            }
+            Command::Remind { once } => {
+                reminder::watch(&config.reminders, *once).map_err(|e| e.to_string())?;
+                Ok(false)
+            }
+            Command::Log { id } => {
+                let task = list
+                    .items()
+                    .iter()
+                    .chain(list.trash().iter().map(|trashed| &trashed.task))
+                    .find(|task| task.id == *id)
+                    .ok_or(format!("No task with id {}", id))?;
+                println!("#{} {}", task.id, task.name);
+                if task.history.is_empty() {
+                    println!("No recorded changes");
+                }
+                for change in &task.history {
+                    println!("{}  {}  {}", change.at.with_timezone(&Local).format("%Y-%m-%d %H:%M"), change.author, change.kind);
+                }
+                Ok(false)
+            }
+            Command::Sync => {
+                let remote = format!("{}/{}", config.sync.remote, config.sync.branch);
+                match sync::sync(&config.sync, &Todo::load_path()).map_err(|e| e.to_string())? {
+                    Synced::UpToDate => println!("Already up to date with {}", remote),
+                    Synced::Pushed => println!("Pushed to {}", remote),
+                    Synced::Pulled => println!("Pulled from {}", remote),
+                    Synced::Merged => println!("Merged with {} and pushed", remote),
+                }
+                Ok(false)
+            }
+            Command::Init => {
+                let dir = std::env::current_dir().map_err(|e| e.to_string())?;
+                let path = project::init(&dir).map_err(|e| e.to_string())?;
+                println!("Created the project list {}", path.display());
+                Ok(false)
+            }
+        }
+    }
+}
+
+fn find(list: &Todo, id: u64) -> Result<usize, String> {
+    list.position(id).ok_or(format!("No task with id {}", id))
+}
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use thiserror::Error;
+
+use crate::crypto::EncryptionSettings;
+use crate::keymap::KeyMap;
+use crate::pomodoro::PomodoroSettings;
+use crate::oplog::OpLogSettings;
+use crate::reminder::ReminderSettings;
+use crate::sync::SyncSettings;
+use crate::theme::Theme;
+use crate::todo::Workflow;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(String),
+}
+
+///User settings, every field is optional in the config file
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    ///Keys overriding the default keymap, by action name
+    pub keymap: KeyMap,
+    ///Name of a built-in theme or path to a theme file
+    #[serde(rename = "theme")]
+    pub theme_name: Option<String>,
+    ///Theme resolved from the theme name
+    #[serde(skip)]
+    pub theme: Theme,
+    ///Asks before removing tasks
+    pub confirm_delete: bool,
+    ///States of the board columns, the last one means done
+    pub workflow: Workflow,
+    ///Lengths of the focus cycles and end of cycle notification
+    pub pomodoro: PomodoroSettings,
+    ///Notifier and check interval of `todo remind`
+    pub reminders: ReminderSettings,
+    ///Git commits of the list and remote of `todo sync`
+    pub sync: SyncSettings,
+    ///Shared folder and replica name of the operation log
+    pub oplog: OpLogSettings,
+    ///Passphrase or key file encrypting the list
+    pub encryption: EncryptionSettings,
+    ///Folders searched for project lists by `todo --all`
+    pub projects: Vec<String>,
+    ///Name matched against assignees by the "mine" filter, $USER when missing
+    pub user: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keymap: KeyMap::default(),
+            theme_name: None,
+            theme: Theme::default(),
+            confirm_delete: true,
+            workflow: Workflow::default(),
+            pomodoro: PomodoroSettings::default(),
+            reminders: ReminderSettings::default(),
+            sync: SyncSettings::default(),
+            oplog: OpLogSettings::default(),
+            encryption: EncryptionSettings::default(),
+            projects: vec![],
+            user: None,
+        }
+    }
+}
+
+impl Config {
+    pub const DEFAULT_PATH: &str = "./config.json";
+    pub const PATH_VAR: &str = "TODO_CONFIG";
+
+    ///Loads the config, a missing file gives the default config
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::read_from_file(&Self::load_path())
+    }
+
+    pub fn load_path() -> String {
+        match env::var(Self::PATH_VAR) {
+            Ok(val) => val,
+            Err(_e) => Self::DEFAULT_PATH.to_string(),
+        }
+    }
+
+    ///Name of the current user, to find the tasks assigned to them
+    pub fn current_user(&self) -> Option<String> {
+        self.user.clone().or_else(|| env::var("USER").ok()).filter(|user| !user.trim().is_empty())
+    }
+
+    fn read_from_file(path: &str) -> Result<Self, ConfigError> {
+        let mut config: Config = match fs::read_to_string(path) {
+            Ok(buff) => serde_json::from_str(&buff)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e.into()),
+        };
+        //User bindings are applied on top of the defaults
+        let mut keymap = KeyMap::default();
+        keymap.merge(config.keymap);
+        config.keymap = keymap;
+        config.theme = Theme::load(config.theme_name.as_deref())?;
+        if !config.workflow.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "workflow needs at least two different states".to_string(),
+            ));
+        }
+        if !config.pomodoro.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "pomodoro cycles must last at least one minute".to_string(),
+            ));
+        }
+        if !config.reminders.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "reminders interval must be at least one second".to_string(),
+            ));
+        }
+        if !config.sync.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "sync needs a remote and a branch".to_string(),
+            ));
+        }
+        if !config.oplog.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "oplog needs a folder, and a replica name made of letters, digits, '-', '_' or '.' when enabled".to_string(),
+            ));
+        }
+        if !config.encryption.is_valid() {
+            return Err(ConfigError::InvalidSetting(
+                "encryption key_file can't be empty".to_string(),
+            ));
+        }
+        //The operation log holds the tasks in plain text
+        if config.encryption.enabled && config.oplog.enabled {
+            return Err(ConfigError::InvalidSetting(
+                "encryption can't be used with the operation log".to_string(),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{Action, Mode};
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn missing_config_is_default() {
+        let config = Config::read_from_file("./does_not_exist.json").unwrap();
+        assert_eq!(config.keymap, KeyMap::default());
+    }
+
+    #[test]
+    fn read_config_keeps_default_keys() {
+        let path = "./test_config.json";
+        fs::write(path, r#"{"keymap": {"quit": ["Ctrl-q"]}}"#).unwrap();
+        let config = Config::read_from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+        let ctrl_q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        let down = KeyEvent::new(KeyCode::Down, KeyModifiers::NONE);
+        assert_eq!(config.keymap.action(Mode::Normal, &ctrl_q), Some(Action::Quit));
+        assert_eq!(config.keymap.action(Mode::Normal, &down), Some(Action::SelectNext));
+    }
+
+    #[test]
+    fn read_config_theme() {
+        let path = "./test_config_theme.json";
+        fs::write(path, r#"{"theme": "high-contrast"}"#).unwrap();
+        let config = Config::read_from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(config.theme, Theme::high_contrast());
+    }
+
+    #[test]
+    fn invalid_workflow_is_an_error() {
+        let path = "./test_config_workflow.json";
+        fs::write(path, r#"{"workflow": ["Done"]}"#).unwrap();
+        let result = Config::read_from_file(path);
+        fs::remove_file(path).unwrap();
+        assert!(matches!(result, Err(ConfigError::InvalidSetting(_))));
+    }
+}
@@ -0,0 +1,166 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+
+///Passphrase or key file contents, set at startup when the list is encrypted
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+///Last key derived from the secret with its salt, deriving takes a noticeable time
+static DERIVED: Mutex<Option<([u8; SALT_LENGTH], Key)>> = Mutex::new(None);
+
+///Version of the encrypted file format
+const FORMAT: u64 = 1;
+
+const SALT_LENGTH: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("the list is encrypted, a passphrase or a key file is needed to read it")]
+    Locked,
+    #[error("wrong passphrase or key file, or the list is damaged")]
+    Decryption,
+    #[error("invalid encrypted list: {0}")]
+    Invalid(String),
+}
+
+///Encryption of the list file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct EncryptionSettings {
+    ///Encrypts the list when it is saved
+    pub enabled: bool,
+    ///File holding the key, the passphrase is asked at startup when missing
+    pub key_file: Option<String>,
+}
+
+impl EncryptionSettings {
+    pub fn is_valid(&self) -> bool {
+        self.key_file.as_ref().is_none_or(|file| !file.trim().is_empty())
+    }
+}
+
+//Encrypted list as written on disk
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Envelope {
+    encrypted: u64,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+///Sets the secret used to read and write the list, lists are encrypted from then on
+pub fn unlock(secret: Vec<u8>) {
+    let _ = SECRET.set(secret);
+}
+
+///Contents of a key file, without the trailing newline editors add
+pub fn read_key_file(path: &str) -> std::io::Result<Vec<u8>> {
+    let mut key = fs::read(path)?;
+    while key.last().is_some_and(u8::is_ascii_whitespace) {
+        key.pop();
+    }
+    if key.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} is empty", path)));
+    }
+    Ok(key)
+}
+
+pub fn is_encrypted(text: &str) -> bool {
+    serde_json::from_str::<Envelope>(text).is_ok()
+}
+
+///Encrypts the text when a secret is set, returns it as is otherwise
+pub fn seal(text: &str) -> Result<String, CryptoError> {
+    match SECRET.get() {
+        Some(secret) => encrypt(secret, text),
+        None => Ok(text.to_string()),
+    }
+}
+
+///Decrypts the text if it is encrypted, returns it as is otherwise
+pub fn open(text: String) -> Result<String, CryptoError> {
+    if !is_encrypted(&text) {
+        return Ok(text);
+    }
+    decrypt(SECRET.get().ok_or(CryptoError::Locked)?, &text)
+}
+
+pub fn encrypt(secret: &[u8], text: &str) -> Result<String, CryptoError> {
+    let salt = match DERIVED.lock().unwrap().as_ref() {
+        Some((salt, _)) => *salt,
+        None => {
+            let mut salt = [0; SALT_LENGTH];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        }
+    };
+    let cipher = XChaCha20Poly1305::new(&derive(secret, salt)?);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let data = cipher.encrypt(&nonce, text.as_bytes()).map_err(|_| CryptoError::Invalid("encryption failed".to_string()))?;
+    let envelope = Envelope {
+        encrypted: FORMAT,
+        kdf: "argon2id".to_string(),
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        data: STANDARD.encode(data),
+    };
+    serde_json::to_string(&envelope).map_err(|e| CryptoError::Invalid(e.to_string()))
+}
+
+pub fn decrypt(secret: &[u8], text: &str) -> Result<String, CryptoError> {
+    let envelope: Envelope = serde_json::from_str(text).map_err(|e| CryptoError::Invalid(e.to_string()))?;
+    if envelope.encrypted != FORMAT || envelope.kdf != "argon2id" {
+        return Err(CryptoError::Invalid(format!("unknown format {} ({})", envelope.encrypted, envelope.kdf)));
+    }
+    let bytes = |field: &str| STANDARD.decode(field).map_err(|e| CryptoError::Invalid(e.to_string()));
+    let salt: [u8; SALT_LENGTH] = bytes(&envelope.salt)?.try_into().map_err(|_| CryptoError::Invalid("bad salt".to_string()))?;
+    let nonce = bytes(&envelope.nonce)?;
+    if nonce.len() != 24 {
+        return Err(CryptoError::Invalid("bad nonce".to_string()));
+    }
+    let cipher = XChaCha20Poly1305::new(&derive(secret, salt)?);
+    let plain = cipher.decrypt(XNonce::from_slice(&nonce), bytes(&envelope.data)?.as_slice()).map_err(|_| CryptoError::Decryption)?;
+    String::from_utf8(plain).map_err(|_| CryptoError::Decryption)
+}
+
+//Key of the secret with the salt of a file, the last one is kept so saving does not derive it again
+fn derive(secret: &[u8], salt: [u8; SALT_LENGTH]) -> Result<Key, CryptoError> {
+    let mut derived = DERIVED.lock().unwrap();
+    if let Some((last, key)) = derived.as_ref() {
+        if *last == salt && SECRET.get().is_some_and(|set| set == secret) {
+            return Ok(*key);
+        }
+    }
+    let mut key = Key::default();
+    Argon2::default().hash_password_into(secret, &salt, &mut key).map_err(|e| CryptoError::Invalid(e.to_string()))?;
+    if SECRET.get().is_some_and(|set| set == secret) {
+        *derived = Some((salt, key));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_lists_need_the_secret() {
+        let list = r#"{"version":1,"list":[{"id":1,"name":"Incident at ACME","priority":9,"done":false}]}"#;
+        let encrypted = encrypt(b"correct horse", list).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("ACME"));
+        assert_eq!(decrypt(b"correct horse", &encrypted).unwrap(), list);
+        assert!(matches!(decrypt(b"wrong horse", &encrypted), Err(CryptoError::Decryption)));
+        //Plain lists are read as they are
+        assert!(!is_encrypted(list));
+        assert_eq!(open(list.to_string()).unwrap(), list);
+    }
+}
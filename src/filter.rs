@@ -0,0 +1,383 @@
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+use thiserror::Error;
+
+use crate::quickadd::parse_date;
+use crate::todo::{Task, Todo};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FilterError {
+    #[error("Unexpected end of filter")]
+    UnexpectedEnd,
+    #[error("Unexpected \"{0}\"")]
+    Unexpected(String),
+    #[error("Invalid value \"{1}\" for {0}")]
+    InvalidValue(String, String),
+    #[error("Unknown condition \"{0}\"")]
+    UnknownCondition(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "=" | "==" => Some(Op::Eq),
+            "!=" => Some(Op::Ne),
+            "<" => Some(Op::Lt),
+            "<=" => Some(Op::Le),
+            ">" => Some(Op::Gt),
+            ">=" => Some(Op::Ge),
+            _ => None,
+        }
+    }
+
+    fn test(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Done,
+    Blocked,
+    Ready,
+    ///Assigned to the current user, replaced by `Assignee` once the user is known
+    Mine,
+    Tag(String),
+    Assignee(String),
+    State(String),
+    Name(String),
+    Id(Op, u64),
+    Priority(Op, u8),
+    ///Dates are kept as written, relative ones like "7d" depend on the day of evaluation
+    Due(Op, String),
+    Created(Op, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Condition(Condition),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+///Expression selecting tasks, like `priority>=5 and not done and tag:backend and due<7d`.
+///
+///Conditions: `done`, `blocked`, `ready`, `mine`, `tag:x` (or `#x`), `assignee:x` (or `@x`), `state:x`,
+///`name:x` (the name contains x), and comparisons (`=`, `!=`, `<`, `<=`, `>`, `>=`) on
+///`id`, `priority`, `due` and `created`. Dates are written like in quick-add: `2024-03-01`,
+///`today`, `fri`, `7d`. They are combined with `and`, `or`, `not` and parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    query: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> Result<Self, FilterError> {
+        let tokens = tokenize(query);
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(FilterError::Unexpected(token.to_string()));
+        }
+        //Dates are checked once so evaluation cannot fail
+        check_dates(&expr)?;
+        Ok(Filter { query: query.trim().to_string(), expr })
+    }
+
+    ///Sets the user matched by `mine`, nothing matches it without a user
+    pub fn with_user(mut self, user: Option<&str>) -> Self {
+        set_user(&mut self.expr, user);
+        self
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn matches(&self, task: &Task, list: &Todo, today: NaiveDate) -> bool {
+        eval(&self.expr, task, list, today)
+    }
+}
+
+//==== Parsing
+
+//Splits words, parentheses and comparison operators, quoted text is one word
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                tokens.push(c.to_string());
+                chars.next();
+            }
+            '<' | '>' | '=' | '!' => {
+                let mut op = String::new();
+                while let Some(&c) = chars.peek().filter(|c| matches!(c, '<' | '>' | '=' | '!')) {
+                    op.push(c);
+                    chars.next();
+                }
+                tokens.push(op);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '<' | '>' | '=' | '!') {
+                        break;
+                    }
+                    chars.next();
+                    if c == '"' {
+                        word.extend(chars.by_ref().take_while(|c| *c != '"'));
+                    } else {
+                        word.push(c);
+                    }
+                }
+                tokens.push(word);
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, FilterError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(FilterError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek().is_some_and(|t| t.eq_ignore_ascii_case(keyword)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, FilterError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, FilterError> {
+        let token = self.next()?;
+        if token == "(" {
+            let expr = self.or()?;
+            return match self.next()?.as_str() {
+                ")" => Ok(expr),
+                other => Err(FilterError::Unexpected(other.to_string())),
+            };
+        }
+        if let Some(op) = self.peek().and_then(Op::parse) {
+            self.position += 1;
+            let value = self.next()?;
+            return comparison(&token, op, value).map(Expr::Condition);
+        }
+        condition(&token).map(Expr::Condition)
+    }
+}
+
+fn comparison(field: &str, op: Op, value: String) -> Result<Condition, FilterError> {
+    let invalid = || FilterError::InvalidValue(field.to_string(), value.clone());
+    match field.to_lowercase().as_str() {
+        "id" => Ok(Condition::Id(op, value.parse().map_err(|_| invalid())?)),
+        "priority" => Ok(Condition::Priority(op, value.parse().map_err(|_| invalid())?)),
+        "due" => Ok(Condition::Due(op, value)),
+        "created" => Ok(Condition::Created(op, value)),
+        _ => Err(FilterError::UnknownCondition(field.to_string())),
+    }
+}
+
+fn condition(token: &str) -> Result<Condition, FilterError> {
+    match token.to_lowercase().as_str() {
+        "done" => return Ok(Condition::Done),
+        "blocked" => return Ok(Condition::Blocked),
+        "ready" => return Ok(Condition::Ready),
+        "mine" => return Ok(Condition::Mine),
+        _ => (),
+    }
+    if let Some(tag) = token.strip_prefix('#') {
+        return Ok(Condition::Tag(tag.to_string()));
+    }
+    if let Some(assignee) = token.strip_prefix('@') {
+        return Ok(Condition::Assignee(assignee.to_string()));
+    }
+    let Some((field, value)) = token.split_once(':') else {
+        return Err(FilterError::UnknownCondition(token.to_string()));
+    };
+    let value = value.to_string();
+    match field.to_lowercase().as_str() {
+        "tag" => Ok(Condition::Tag(value)),
+        "assignee" => Ok(Condition::Assignee(value)),
+        "state" => Ok(Condition::State(value)),
+        "name" => Ok(Condition::Name(value)),
+        _ => Err(FilterError::UnknownCondition(token.to_string())),
+    }
+}
+
+fn check_dates(expr: &Expr) -> Result<(), FilterError> {
+    match expr {
+        Expr::Condition(Condition::Due(_, date)) | Expr::Condition(Condition::Created(_, date)) => {
+            //Any day works to check the syntax
+            match parse_date(date, NaiveDate::from_ymd_opt(2000, 1, 1).unwrap()) {
+                Some(_) => Ok(()),
+                None => Err(FilterError::InvalidValue("date".to_string(), date.clone())),
+            }
+        }
+        Expr::Condition(_) => Ok(()),
+        Expr::Not(expr) => check_dates(expr),
+        Expr::And(a, b) | Expr::Or(a, b) => check_dates(a).and(check_dates(b)),
+    }
+}
+
+fn set_user(expr: &mut Expr, user: Option<&str>) {
+    match expr {
+        Expr::Condition(condition @ Condition::Mine) => {
+            if let Some(user) = user {
+                *condition = Condition::Assignee(user.to_string());
+            }
+        }
+        Expr::Condition(_) => (),
+        Expr::Not(expr) => set_user(expr, user),
+        Expr::And(a, b) | Expr::Or(a, b) => {
+            set_user(a, user);
+            set_user(b, user);
+        }
+    }
+}
+
+//==== Evaluation
+
+fn eval(expr: &Expr, task: &Task, list: &Todo, today: NaiveDate) -> bool {
+    match expr {
+        Expr::Not(expr) => !eval(expr, task, list, today),
+        Expr::And(a, b) => eval(a, task, list, today) && eval(b, task, list, today),
+        Expr::Or(a, b) => eval(a, task, list, today) || eval(b, task, list, today),
+        Expr::Condition(condition) => match condition {
+            Condition::Done => task.done,
+            Condition::Blocked => list.is_blocked(task),
+            Condition::Ready => !task.done && !list.is_blocked(task),
+            Condition::Mine => false,
+            Condition::Tag(tag) => task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Condition::Assignee(name) => task.is_assigned_to(name),
+            Condition::State(state) => task.state.as_ref().is_some_and(|s| s.eq_ignore_ascii_case(state)),
+            Condition::Name(text) => task.name.to_lowercase().contains(&text.to_lowercase()),
+            Condition::Id(op, id) => op.test(task.id.cmp(id)),
+            Condition::Priority(op, priority) => op.test(task.priority.cmp(priority)),
+            Condition::Due(op, date) => compare_date(task.due, *op, date, today),
+            Condition::Created(op, date) => {
+                let created = task.created.map(|created| created.with_timezone(&chrono::Local).date_naive());
+                compare_date(created, *op, date, today)
+            }
+        },
+    }
+}
+
+//Tasks without the date never match
+fn compare_date(value: Option<NaiveDate>, op: Op, date: &str, today: NaiveDate) -> bool {
+    match (value, parse_date(date, today)) {
+        (Some(value), Some(date)) => op.test(value.cmp(&date)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn names(list: &Todo, query: &str, today: NaiveDate) -> Vec<String> {
+        let filter = Filter::parse(query).unwrap();
+        list.items()
+            .iter()
+            .filter(|task| filter.matches(task, list, today))
+            .map(|task| task.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn filter_tasks() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let mut list = Todo::new();
+        list.add("Api", 7);
+        list.add("Docs", 2);
+        list.add("Old", 9);
+        list.update(list.position(1).unwrap(), |task| {
+            task.tags = vec!["backend".to_string()];
+            task.due = Some(today + TimeDelta::days(3));
+            task.assignee = Some("Ann".to_string());
+        });
+        list.update(list.position(2).unwrap(), |task| task.due = Some(today + TimeDelta::days(30)));
+        list.set_done(list.position(3).unwrap(), true);
+
+        assert_eq!(names(&list, "priority>=5 and not done and tag:backend and due<7d", today), vec!["Api"]);
+        assert_eq!(names(&list, "done or due > 2w", today), vec!["Docs", "Old"]);
+        assert_eq!(names(&list, "ready", today), vec!["Api", "Docs"]);
+        assert_eq!(names(&list, "not (priority < 5 or done)", today), vec!["Api"]);
+        assert_eq!(names(&list, "name:\"do\" OR #BACKEND", today), vec!["Api", "Docs"]);
+        assert_eq!(names(&list, "id != 1 and priority = 2", today), vec!["Docs"]);
+        assert!(names(&list, "mine", today).is_empty());
+        let mine = Filter::parse("mine or done").unwrap().with_user(Some("ann"));
+        let matching: Vec<&str> = list.items().iter().filter(|task| mine.matches(task, &list, today)).map(|task| task.name.as_str()).collect();
+        assert_eq!(matching, vec!["Api", "Old"]);
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert_eq!(Filter::parse("priority >= high"), Err(FilterError::InvalidValue("priority".to_string(), "high".to_string())));
+        assert_eq!(Filter::parse("done and"), Err(FilterError::UnexpectedEnd));
+        assert_eq!(Filter::parse("(done"), Err(FilterError::UnexpectedEnd));
+        assert_eq!(Filter::parse("done done"), Err(FilterError::Unexpected("done".to_string())));
+        assert_eq!(Filter::parse("colour:red"), Err(FilterError::UnknownCondition("colour:red".to_string())));
+        assert!(Filter::parse("due < someday").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+///Author of the changes made by this program, set at startup
+static AUTHOR: OnceLock<String> = OnceLock::new();
+
+///Fields left out of the history, they change too often or come with another change
+const IGNORED: [&str; 5] = ["id", "history", "time_entries", "pomodoros", "completed"];
+
+///One change in the history of a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub at: DateTime<Utc>,
+    pub author: String,
+    #[serde(flatten)]
+    pub kind: ChangeKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Renamed { from: String, to: String },
+    Priority { from: u8, to: u8 },
+    Done,
+    Reopened,
+    Removed,
+    Restored,
+    ///Any other field, missing values are null
+    Field { field: String, from: Value, to: Value },
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Created => write!(f, "created"),
+            ChangeKind::Renamed { from, to } => write!(f, "renamed from \"{}\" to \"{}\"", from, to),
+            ChangeKind::Priority { from, to } => write!(f, "priority {}→{}", from, to),
+            ChangeKind::Done => write!(f, "marked done"),
+            ChangeKind::Reopened => write!(f, "marked not done"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Restored => write!(f, "restored"),
+            ChangeKind::Field { field, from, to } => write!(f, "{} {}→{}", field.replace('_', " "), show(from), show(to)),
+        }
+    }
+}
+
+//Value written for humans: no quotes, lists separated by commas
+fn show(value: &Value) -> String {
+    match value {
+        Value::Null => "none".to_string(),
+        Value::String(text) => text.clone(),
+        Value::Array(values) if values.is_empty() => "none".to_string(),
+        Value::Array(values) => values.iter().map(show).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+///Sets the author written in the history, $USER is used when it is not set
+pub fn set_author(author: Option<String>) {
+    if let Some(author) = author {
+        let _ = AUTHOR.set(author);
+    }
+}
+
+pub fn author() -> String {
+    match AUTHOR.get() {
+        Some(author) => author.clone(),
+        None => env::var("USER").unwrap_or("unknown".to_string()),
+    }
+}
+
+///Changes between two versions of a task, saved as JSON
+pub fn changes(before: &Value, after: &Value) -> Vec<ChangeKind> {
+    let mut changes = vec![];
+    let (Some(old), Some(new)) = (before.as_object(), after.as_object()) else {
+        return changes;
+    };
+    for (field, value) in new {
+        let previous = old.get(field).unwrap_or(&Value::Null);
+        if previous == value || IGNORED.contains(&field.as_str()) {
+            continue;
+        }
+        changes.push(match (field.as_str(), previous, value) {
+            ("name", Value::String(from), Value::String(to)) => ChangeKind::Renamed { from: from.clone(), to: to.clone() },
+            ("priority", Value::Number(from), Value::Number(to)) => ChangeKind::Priority {
+                from: from.as_u64().unwrap_or(0) as u8,
+                to: to.as_u64().unwrap_or(0) as u8,
+            },
+            ("done", _, Value::Bool(true)) => ChangeKind::Done,
+            ("done", _, _) => ChangeKind::Reopened,
+            _ => ChangeKind::Field { field: field.clone(), from: previous.clone(), to: value.clone() },
+        });
+    }
+    changes
+}
+
+///Union of two histories of a task as saved in JSON, each change once, in the order they were made.
+///Histories only grow, so replicas merge them instead of keeping one side
+pub fn merge(ours: &Value, theirs: &Value) -> Value {
+    let mut changes: Vec<Change> = [ours, theirs]
+        .into_iter()
+        .filter_map(|history| serde_json::from_value::<Vec<Change>>(history.clone()).ok())
+        .flatten()
+        .collect();
+    changes.sort_by_key(|change| change.at);
+    let mut merged: Vec<Change> = vec![];
+    for change in changes {
+        if !merged.contains(&change) {
+            merged.push(change);
+        }
+    }
+    serde_json::to_value(merged).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn changes_between_versions() {
+        let before = json!({ "id": 1, "name": "Fix bug", "priority": 3, "done": false, "tags": [], "due": null, "completed": null });
+        let after = json!({ "id": 1, "name": "Fix login bug", "priority": 7, "done": true, "tags": ["api", "ops"], "due": null, "completed": "2024-03-01T10:00:00Z" });
+        let changes: Vec<String> = changes(&before, &after).iter().map(|change| change.to_string()).collect();
+        assert_eq!(changes, vec!["marked done", "renamed from \"Fix bug\" to \"Fix login bug\"", "priority 3→7", "tags none→api, ops"]);
+        assert!(super::changes(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn histories_are_merged() {
+        let created = json!({ "at": "2024-03-01T10:00:00Z", "author": "ann", "change": "created" });
+        let done = json!({ "at": "2024-03-02T10:00:00Z", "author": "ann", "change": "done" });
+        let renamed = json!({ "at": "2024-03-01T12:00:00Z", "author": "bob", "change": "renamed", "from": "Fix", "to": "Fix bug" });
+        let merged = merge(&json!([created, done]), &json!([created, renamed]));
+        assert_eq!(merged, json!([created, renamed, done]));
+        assert_eq!(merge(&merged, &Value::Null), merged);
+    }
+}
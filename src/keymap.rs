@@ -0,0 +1,471 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+//==== Action
+
+///Mode in which an action can be triggered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Edit,
+    ///Typing a single value in a prompt
+    Input,
+    ///Answering a yes/no question
+    Confirm,
+    ///Browsing the removed tasks
+    Trash,
+    ///Looking at the statistics
+    Stats,
+    ///Moving cards on the board
+    Board,
+}
+
+///Named action a key can be bound to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    SelectNone,
+    SelectNext,
+    SelectPrevious,
+    SelectFirst,
+    SelectLast,
+    AddTask,
+    RemoveTask,
+    Edit,
+    ToggleDone,
+    CycleSort,
+    MoveUp,
+    MoveDown,
+    ToggleMark,
+    MarkRange,
+    ClearMarks,
+    PriorityUp,
+    PriorityDown,
+    AddTag,
+    SetDependencies,
+    ToggleReady,
+    ToggleMine,
+    GroupByAssignee,
+    ToggleTimer,
+    TogglePomodoro,
+    Filter,
+    SaveView,
+    ToggleTrash,
+    ToggleStats,
+    ToggleBoard,
+    ColumnLeft,
+    ColumnRight,
+    MoveCardLeft,
+    MoveCardRight,
+    RestoreTask,
+    EmptyTrash,
+    Confirm,
+    Cancel,
+    SaveEdit,
+    CancelEdit,
+    IncreasePriority,
+    DecreasePriority,
+    EraseText,
+}
+
+impl Action {
+    ///All actions, in the order they are displayed in the help
+    pub const ALL: [Action; 43] = [
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::SelectNone,
+        Action::SelectFirst,
+        Action::SelectLast,
+        Action::ToggleDone,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::CycleSort,
+        Action::ToggleMark,
+        Action::MarkRange,
+        Action::ClearMarks,
+        Action::PriorityUp,
+        Action::PriorityDown,
+        Action::AddTag,
+        Action::SetDependencies,
+        Action::ToggleReady,
+        Action::ToggleMine,
+        Action::GroupByAssignee,
+        Action::ToggleTimer,
+        Action::TogglePomodoro,
+        Action::Filter,
+        Action::SaveView,
+        Action::AddTask,
+        Action::Edit,
+        Action::RemoveTask,
+        Action::ToggleTrash,
+        Action::ToggleStats,
+        Action::ToggleBoard,
+        Action::ColumnLeft,
+        Action::ColumnRight,
+        Action::MoveCardLeft,
+        Action::MoveCardRight,
+        Action::RestoreTask,
+        Action::EmptyTrash,
+        Action::Quit,
+        Action::Confirm,
+        Action::Cancel,
+        Action::SaveEdit,
+        Action::CancelEdit,
+        Action::IncreasePriority,
+        Action::DecreasePriority,
+        Action::EraseText,
+    ];
+
+    ///Modes in which the action can be triggered
+    pub fn modes(&self) -> &'static [Mode] {
+        match self {
+            Action::SaveEdit | Action::CancelEdit | Action::EraseText => &[Mode::Edit, Mode::Input],
+            Action::IncreasePriority | Action::DecreasePriority => &[Mode::Edit],
+            Action::Confirm | Action::Cancel => &[Mode::Confirm],
+            Action::RestoreTask | Action::EmptyTrash => &[Mode::Trash],
+            Action::ColumnLeft | Action::ColumnRight | Action::MoveCardLeft | Action::MoveCardRight => &[Mode::Board],
+            Action::SelectNext | Action::SelectPrevious => &[Mode::Normal, Mode::Trash, Mode::Board],
+            Action::SelectNone | Action::SelectFirst | Action::SelectLast => &[Mode::Normal, Mode::Trash],
+            Action::ToggleTrash | Action::ToggleStats | Action::ToggleBoard | Action::Quit => {
+                &[Mode::Normal, Mode::Trash, Mode::Stats, Mode::Board]
+            }
+            _ => &[Mode::Normal],
+        }
+    }
+
+    ///Short text displayed in the footer help
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::SelectNone => "unselect",
+            Action::SelectNext => "down",
+            Action::SelectPrevious => "up",
+            Action::SelectFirst => "first",
+            Action::SelectLast => "last",
+            Action::AddTask => "add",
+            Action::RemoveTask => "remove",
+            Action::Edit => "edit",
+            Action::ToggleDone => "change status",
+            Action::CycleSort => "sort",
+            Action::MoveUp => "move up",
+            Action::MoveDown => "move down",
+            Action::ToggleMark => "mark",
+            Action::MarkRange => "mark range",
+            Action::ClearMarks => "unmark all",
+            Action::PriorityUp => "priority +",
+            Action::PriorityDown => "priority -",
+            Action::AddTag => "tag",
+            Action::SetDependencies => "dependencies",
+            Action::ToggleReady => "ready to work",
+            Action::ToggleMine => "my tasks",
+            Action::GroupByAssignee => "group by assignee",
+            Action::ToggleTimer => "start/stop timer",
+            Action::TogglePomodoro => "pomodoro",
+            Action::Filter => "filter",
+            Action::SaveView => "save view",
+            Action::ToggleTrash => "trash",
+            Action::ToggleStats => "statistics",
+            Action::ToggleBoard => "board",
+            Action::ColumnLeft => "left column",
+            Action::ColumnRight => "right column",
+            Action::MoveCardLeft => "move card left",
+            Action::MoveCardRight => "move card right",
+            Action::RestoreTask => "restore",
+            Action::EmptyTrash => "empty trash",
+            Action::Confirm => "yes",
+            Action::Cancel => "no",
+            Action::SaveEdit => "save",
+            Action::CancelEdit => "cancel",
+            Action::IncreasePriority => "priority +",
+            Action::DecreasePriority => "priority -",
+            Action::EraseText => "erase",
+        }
+    }
+}
+
+//==== KeyBinding
+
+///A key with its modifiers, written like "j", "Down" or "Ctrl-n" in the config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        KeyBinding { code, modifiers }
+    }
+
+    ///Returns true if the key event triggers this binding
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let mut modifiers = key.modifiers;
+        if let KeyCode::Char(_) = key.code {
+            //Shift is already part of the character ('G' vs 'g')
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        self.code == key.code && self.modifiers == modifiers
+    }
+
+    ///Text displayed in the help, arrows are shown as symbols
+    pub fn label(&self) -> String {
+        let symbol = match self.code {
+            KeyCode::Up => "↑",
+            KeyCode::Down => "↓",
+            KeyCode::Left => "←",
+            KeyCode::Right => "→",
+            _ => return self.to_string(),
+        };
+        format!("{}{}", self.modifiers_prefix(), symbol)
+    }
+
+    fn modifiers_prefix(&self) -> String {
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("Ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("Alt-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            prefix.push_str("Shift-");
+        }
+        prefix
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.as_str().parse()
+    }
+}
+
+impl std::str::FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = text;
+        //"-" alone is a key, "Ctrl--" is Ctrl with "-"
+        while let Some((prefix, rest)) = key.split_once('-') {
+            if rest.is_empty() {
+                break;
+            }
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", prefix, text)),
+            };
+            key = rest;
+        }
+        let code = match key.to_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('f') => match f[1..].parse::<u8>() {
+                Ok(n) => KeyCode::F(n),
+                Err(_) => return Err(format!("Unknown key '{}'", text)),
+            },
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("Unknown key '{}'", text)),
+                }
+            }
+        };
+        Ok(KeyBinding { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.modifiers_prefix())?;
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(binding: KeyBinding) -> Self {
+        binding.to_string()
+    }
+}
+
+//==== KeyMap
+
+///Associates actions to the keys triggering them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyMap {
+    #[serde(flatten)]
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut keymap = KeyMap {
+            bindings: HashMap::new(),
+        };
+        for action in Action::ALL {
+            let keys: &[&str] = match action {
+                Action::Quit => &["q", "Esc"],
+                Action::SelectNone => &["h", "Left"],
+                Action::SelectNext => &["j", "Down"],
+                Action::SelectPrevious => &["k", "Up"],
+                Action::SelectFirst => &["g", "Home"],
+                Action::SelectLast => &["G", "End"],
+                Action::AddTask => &["a"],
+                Action::RemoveTask => &["Delete"],
+                Action::Edit => &["Enter"],
+                Action::ToggleDone => &["l", "Right"],
+                Action::CycleSort => &["s"],
+                Action::MoveUp => &["K", "Shift-Up"],
+                Action::MoveDown => &["J", "Shift-Down"],
+                Action::ToggleMark => &["Space"],
+                Action::MarkRange => &["v"],
+                Action::ClearMarks => &["u"],
+                Action::PriorityUp => &["+"],
+                Action::PriorityDown => &["-"],
+                Action::AddTag => &["t"],
+                Action::SetDependencies => &["d"],
+                Action::ToggleReady => &["R"],
+                Action::ToggleMine => &["M"],
+                Action::GroupByAssignee => &["A"],
+                Action::ToggleTimer => &["m"],
+                Action::TogglePomodoro => &["p"],
+                Action::Filter => &["/"],
+                Action::SaveView => &["V"],
+                Action::ToggleTrash => &["T"],
+                Action::ToggleStats => &["S"],
+                Action::ToggleBoard => &["B"],
+                Action::ColumnLeft => &["h", "Left"],
+                Action::ColumnRight => &["l", "Right"],
+                Action::MoveCardLeft => &["H", "Shift-Left"],
+                Action::MoveCardRight => &["L", "Shift-Right"],
+                Action::RestoreTask => &["r", "Enter"],
+                Action::EmptyTrash => &["E"],
+                Action::Confirm => &["y", "Enter"],
+                Action::Cancel => &["n", "Esc"],
+                Action::SaveEdit => &["Enter"],
+                Action::CancelEdit => &["Esc"],
+                Action::IncreasePriority => &["+"],
+                Action::DecreasePriority => &["-"],
+                Action::EraseText => &["Backspace"],
+            };
+            keymap.bind(action, keys.iter().map(|k| k.parse().unwrap()).collect());
+        }
+        keymap
+    }
+}
+
+impl KeyMap {
+    ///Replaces the keys bound to an action
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyBinding>) {
+        self.bindings.insert(action, keys);
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    ///Returns the action triggered by the key in the given mode
+    pub fn action(&self, mode: Mode, key: &KeyEvent) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|action| action.modes().contains(&mode))
+            .find(|action| self.keys(*action).iter().any(|k| k.matches(key)))
+    }
+
+    ///Applies the bindings of another keymap on top of this one
+    pub fn merge(&mut self, other: KeyMap) {
+        self.bindings.extend(other.bindings);
+    }
+
+    ///Returns "keys: description" entries for every bound action of the mode
+    pub fn help(&self, mode: Mode) -> Vec<String> {
+        Action::ALL
+            .into_iter()
+            .filter(|action| action.modes().contains(&mode) && !self.keys(*action).is_empty())
+            .map(|action| {
+                let keys: Vec<String> = self.keys(action).iter().map(|k| k.label()).collect();
+                format!("{}: {}", keys.join("/"), action.description())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parse_key_bindings() {
+        assert_eq!("j".parse(), Ok(KeyBinding::new(KeyCode::Char('j'), KeyModifiers::NONE)));
+        assert_eq!("Down".parse(), Ok(KeyBinding::new(KeyCode::Down, KeyModifiers::NONE)));
+        assert_eq!("Ctrl-n".parse(), Ok(KeyBinding::new(KeyCode::Char('n'), KeyModifiers::CONTROL)));
+        assert_eq!("-".parse(), Ok(KeyBinding::new(KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!("F5".parse(), Ok(KeyBinding::new(KeyCode::F(5), KeyModifiers::NONE)));
+        assert!("Hyper-x".parse::<KeyBinding>().is_err());
+        assert!("Nope".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn default_keymap_actions() {
+        let keymap = KeyMap::default();
+        let enter = press(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(keymap.action(Mode::Normal, &enter), Some(Action::Edit));
+        assert_eq!(keymap.action(Mode::Edit, &enter), Some(Action::SaveEdit));
+        let shift_g = press(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(Mode::Normal, &shift_g), Some(Action::SelectLast));
+        let x = press(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Mode::Normal, &x), None);
+    }
+
+    #[test]
+    fn user_keymap_overrides_defaults() {
+        let mut keymap = KeyMap::default();
+        let user: KeyMap = serde_json::from_str(r#"{"select_next": ["Ctrl-n"]}"#).unwrap();
+        keymap.merge(user);
+        let ctrl_n = press(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        let j = press(KeyCode::Char('j'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Mode::Normal, &ctrl_n), Some(Action::SelectNext));
+        assert_eq!(keymap.action(Mode::Normal, &j), None);
+        assert!(keymap.help(Mode::Normal).contains(&"Ctrl-n: down".to_string()));
+    }
+
+    #[test]
+    fn key_binding_round_trip() {
+        let keymap = KeyMap::default();
+        let json = serde_json::to_string(&keymap).unwrap();
+        let read: KeyMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(keymap, read);
+    }
+}
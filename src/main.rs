@@ -1,30 +1,157 @@
-use dialoguer::{theme::ColorfulTheme, Confirm};
+use dialoguer::{theme::ColorfulTheme, Confirm, Password};
+use clap::Parser;
 use color_eyre::Result;
 
 mod todo;
 mod app;
 mod ui;
+mod config;
+mod keymap;
+mod theme;
+mod stats;
+mod report;
+mod cli;
+mod notify;
+mod pomodoro;
+mod reminder;
+mod quickadd;
+mod server;
+mod rpc;
+mod output;
+mod filter;
+mod sync;
+mod oplog;
+mod history;
+mod schema;
+mod crypto;
+mod project;
 use todo::{Todo,TodoFileError};
 use app::App;
+use config::Config;
+use crypto::EncryptionSettings;
+use cli::{Cli, Command};
 
 
 
 fn create_empty_list() -> Todo {
-    let new = Todo::new();
+    let mut new = Todo::new();
     if let Err(save_error) = new.save() {
-        println!("Failed to save the new list: {}", save_error);
+        eprintln!("Failed to save the new list: {}", save_error);
     } else {
-        println!("New empty list generated");
+        eprintln!("New empty list generated");
     }
     new
 }
 
+//Gets the key file or asks the passphrase when the list is encrypted or will be,
+//returns true if the list on disk is still in plain text
+fn unlock(settings: &EncryptionSettings) -> std::result::Result<bool, String> {
+    let path = Todo::load_path();
+    let text = std::fs::read_to_string(&path).ok();
+    let encrypted = text.as_deref().is_some_and(crypto::is_encrypted);
+    if !settings.enabled && !encrypted {
+        return Ok(false);
+    }
+    if let Some(key_file) = &settings.key_file {
+        let key = crypto::read_key_file(key_file).map_err(|e| format!("Could not read the key file {} : {}", key_file, e))?;
+        crypto::unlock(key);
+        return Ok(text.is_some() && !encrypted);
+    }
+    let theme = ColorfulTheme::default();
+    let Some(text) = text.filter(|_| encrypted) else {
+        let passphrase = Password::with_theme(&theme)
+            .with_prompt(format!("New passphrase for {}", path))
+            .with_confirmation("Repeat the passphrase", "The passphrases don't match")
+            .interact()
+            .map_err(|e| e.to_string())?;
+        crypto::unlock(passphrase.into_bytes());
+        return Ok(std::path::Path::new(&path).exists());
+    };
+    for _ in 0..3 {
+        let passphrase = Password::with_theme(&theme)
+            .with_prompt(format!("Passphrase of {}", path))
+            .interact()
+            .map_err(|e| e.to_string())?;
+        match crypto::decrypt(passphrase.as_bytes(), &text) {
+            Ok(_) => {
+                crypto::unlock(passphrase.into_bytes());
+                return Ok(false);
+            }
+            Err(crypto::CryptoError::Decryption) => eprintln!("Wrong passphrase"),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Err(format!("Could not decrypt {}", path))
+}
+
+//Loads the lists found from the current directory and in the search folders, with the global list
+fn load_all(config: &Config) -> std::result::Result<Todo, String> {
+    if config.oplog.enabled {
+        return Err("--all can't be used with the operation log".to_string());
+    }
+    let dir = std::env::current_dir().map_err(|e| e.to_string())?;
+    let paths: Vec<String> = project::discover(&dir, &config.projects)
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if paths.is_empty() {
+        return Err("No list found, create one with `todo init`".to_string());
+    }
+    Todo::load_all(&paths).map_err(|e| format!("Could not read the lists : {}", e))
+}
+
+fn run_app(list: Todo, config: Config) -> Result<()> {
+    let app: App = App::with_config(list, config);
+
+    color_eyre::install()?;
+    let terminal = ratatui::init();
+    let app_result = app.run(terminal);
+    ratatui::restore();
+    app_result
+}
+
 fn main() -> Result<()> {
-    let list: Todo;
+    let cli = Cli::parse();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Could not read {}, default settings will be used.\nError : {}", Config::load_path(), e);
+            Config::default()
+        }
+    };
+    sync::enable(&config.sync);
+    oplog::enable(&config.oplog);
+    history::set_author(config.current_user());
+    let plain = match unlock(&config.encryption) {
+        Ok(plain) => plain,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    //A project list is created without reading the current list
+    if let Some(command @ Command::Init) = &cli.command {
+        if let Err(e) = command.run(&mut Todo::new(), &config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if cli.all {
+        match load_all(&config) {
+            Ok(list) => return run_app(list, config),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut list: Todo;
     match Todo::load() {
         Ok(todo) => list = todo,
         Err(TodoFileError::IoError(e)) => {
-            println!(
+            eprintln!(
                 "Could not read {}, a new empty list will be created.\nError : {}",
                 Todo::load_path(),
                 e
@@ -36,29 +163,54 @@ fn main() -> Result<()> {
                 "Parsing error while reading {}, a new empty list will be created.",
                 Todo::load_path()
             );
-            println!("{}", error);
-            println!("Error is : {}", e);
-            println!("By creating the new list, the previous data will be erased");
+            eprintln!("{}", error);
+            eprintln!("Error is : {}", e);
+            let backup = format!("{}.bak", Todo::load_path());
+            eprintln!("By creating the new list, the previous data will be erased (a copy is kept in {})", backup);
 
             if Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt("Do you want to continue?")
                 .interact()
                 .unwrap()
             {
+                if let Err(e) = std::fs::copy(Todo::load_path(), &backup) {
+                    eprintln!("Could not copy the list to {} : {}", backup, e);
+                    std::process::exit(1);
+                }
                 list = create_empty_list();
             } else {
-                println!("Exiting...");
+                eprintln!("Exiting...");
                 //return Err(());
                 panic!();
             }
         }
+        Err(e) => {
+            eprintln!("Could not read {} : {}", Todo::load_path(), e);
+            std::process::exit(1);
+        }
     };
+    //A list in plain text is encrypted as soon as encryption is enabled
+    if plain {
+        if let Err(e) = list.save() {
+            eprintln!("Failed to encrypt {} : {}", Todo::load_path(), e);
+        }
+    }
 
-    let app: App = App::new(list);
+    if let Some(command) = cli.command {
+        match command.run(&mut list, &config) {
+            Ok(true) => {
+                if let Err(e) = list.save() {
+                    eprintln!("Failed to save {} : {}", Todo::load_path(), e);
+                }
+            }
+            Ok(false) => (),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-    color_eyre::install()?;
-    let terminal = ratatui::init();
-    let app_result = app.run(terminal);
-    ratatui::restore();
-    app_result
+    run_app(list, config)
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+use std::process::{Command, Stdio};
+use std::thread;
+
+///Environment variable giving its message to a notification command
+pub const MESSAGE_VAR: &str = "TODO_MESSAGE";
+
+///Shell command of the user, run with the message in $TODO_MESSAGE
+pub fn command(command: &str, message: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).env(MESSAGE_VAR, message).stdin(Stdio::null());
+    shell
+}
+
+///Runs a command without blocking the caller, it is waited for on its own thread so it doesn't
+///stay as a zombie process. Failures are ignored
+pub fn run_detached(mut command: Command) {
+    thread::spawn(move || {
+        let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_get_the_message() {
+        let output = command("printf %s \"$TODO_MESSAGE\"", "Break over").output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "Break over");
+    }
+}
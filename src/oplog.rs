@@ -0,0 +1,452 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::history;
+use crate::todo::{Todo, TodoFileError};
+
+///Set at startup when the operation log is enabled
+static SETTINGS: OnceLock<OpLogSettings> = OnceLock::new();
+
+///Where the operations of the list are logged
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OpLogSettings {
+    ///Records every change of the list in the log and rebuilds the list from it
+    pub enabled: bool,
+    ///Shared folder holding one log file per replica
+    pub dir: String,
+    ///Name of this machine in the log, required when the log is enabled
+    pub replica: Option<String>,
+}
+
+impl Default for OpLogSettings {
+    fn default() -> Self {
+        OpLogSettings { enabled: false, dir: "./oplog".to_string(), replica: None }
+    }
+}
+
+impl OpLogSettings {
+    ///An enabled log needs a replica name, machines sharing one would overwrite each other's operations
+    pub fn is_valid(&self) -> bool {
+        let valid_name = |name: &String| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        };
+        !self.dir.trim().is_empty()
+            && self.replica.as_ref().is_none_or(valid_name)
+            && (!self.enabled || self.replica.is_some())
+    }
+
+    ///Name of this machine in the log, empty when missing, which is only valid while the log is disabled
+    pub fn replica_name(&self) -> &str {
+        self.replica.as_deref().unwrap_or_default()
+    }
+}
+
+///Identifies an operation: the replica that made it and its number on that replica
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OpId {
+    pub replica: String,
+    pub seq: u64,
+}
+
+///One change of the list. Operations are ordered by logical clock, then by id,
+///so every replica replays the same log the same way
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Op {
+    pub id: OpId,
+    ///Lamport clock, greater than the clock of every operation known when it was made
+    pub clock: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: OpKind,
+}
+
+///Operations on tasks, a task is identified by the operation that added it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum OpKind {
+    Add { task: Value },
+    Rename { target: OpId, name: String },
+    SetPriority { target: OpId, priority: u8 },
+    Done { target: OpId, done: bool },
+    ///Any other field of the task
+    Set { target: OpId, field: String, value: Value },
+    ///Changes added to the history of the task, histories of replicas are merged instead of replaced
+    History { target: OpId, changes: Vec<Value> },
+    Remove { target: OpId },
+    Restore { target: OpId },
+    Purge { target: OpId },
+}
+
+impl Op {
+    fn key(&self) -> (u64, &str, u64) {
+        (self.clock, &self.id.replica, self.id.seq)
+    }
+}
+
+///Tasks rebuilt from a log
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct State {
+    ///Tasks of the list, in the order they were added
+    pub list: Vec<Value>,
+    ///Removed tasks, as saved in the trash
+    pub trash: Vec<Value>,
+    ///Operation that added each task, by task id
+    pub origins: BTreeMap<u64, OpId>,
+}
+
+impl State {
+    //Tasks of a list, without origins
+    fn of(list: &Todo) -> Result<Self, TodoFileError> {
+        let value = serde_json::to_value(list)?;
+        let array = |key: &str| value[key].as_array().cloned().unwrap_or_default();
+        Ok(State { list: array("list"), trash: array("trash"), origins: BTreeMap::new() })
+    }
+
+    //A task and whether it is in the trash
+    fn find(&self, origin: &OpId) -> Option<(&Value, bool)> {
+        let id = self.origins.iter().find(|(_, o)| *o == origin)?.0;
+        self.get(*id)
+    }
+
+    fn get(&self, id: u64) -> Option<(&Value, bool)> {
+        let in_list = self.list.iter().find(|task| task["id"] == id).map(|task| (task, false));
+        in_list.or_else(|| self.trash.iter().find(|trashed| trashed["task"]["id"] == id).map(|trashed| (&trashed["task"], true)))
+    }
+}
+
+///Operations of every replica, kept in replay order
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OpLog {
+    ops: Vec<Op>,
+}
+
+impl OpLog {
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    ///Adds the operations of another log. Merging is commutative and idempotent
+    pub fn merge(&mut self, other: OpLog) {
+        self.ops.extend(other.ops);
+        self.ops.sort_by(|a, b| a.key().cmp(&b.key()));
+        self.ops.dedup_by(|a, b| a.id == b.id);
+    }
+
+    ///Greatest clock of the log
+    pub fn clock(&self) -> u64 {
+        self.ops.iter().map(|op| op.clock).max().unwrap_or(0)
+    }
+
+    ///Replays the operations. A task added with an id already taken gets the next free one
+    pub fn replay(&self) -> State {
+        let mut tasks: BTreeMap<&OpId, (Value, Option<DateTime<Utc>>)> = BTreeMap::new();
+        let mut order: Vec<&OpId> = vec![];
+        let mut taken: BTreeSet<u64> = BTreeSet::new();
+        for op in &self.ops {
+            let (target, field, value) = match &op.kind {
+                OpKind::Add { task } => {
+                    let proposed = task["id"].as_u64().filter(|id| *id > 0 && !taken.contains(id));
+                    let id = proposed.unwrap_or(taken.last().map_or(1, |max| max + 1));
+                    taken.insert(id);
+                    let mut task = task.clone();
+                    task["id"] = json!(id);
+                    tasks.insert(&op.id, (task, None));
+                    order.push(&op.id);
+                    continue;
+                }
+                OpKind::Rename { target, name } => (target, "name", json!(name)),
+                OpKind::SetPriority { target, priority } => (target, "priority", json!(priority)),
+                OpKind::Done { target, done } => (target, "done", json!(done)),
+                OpKind::Set { target, field, value } => (target, field.as_str(), value.clone()),
+                OpKind::Remove { target } => {
+                    if let Some(entry) = tasks.get_mut(target) {
+                        entry.1 = entry.1.or(Some(op.at));
+                    }
+                    continue;
+                }
+                OpKind::Restore { target } => {
+                    if let Some(entry) = tasks.get_mut(target) {
+                        entry.1 = None;
+                    }
+                    continue;
+                }
+                OpKind::Purge { target } => {
+                    tasks.remove(target);
+                    continue;
+                }
+                OpKind::History { target, changes } => {
+                    if let Some((task, _)) = tasks.get_mut(target) {
+                        task["history"] = history::merge(&task["history"], &Value::Array(changes.clone()));
+                    }
+                    continue;
+                }
+            };
+            if let Some((task, _)) = tasks.get_mut(target) {
+                task[field] = value;
+            }
+        }
+
+        let mut state = State::default();
+        for origin in order {
+            let Some((task, deleted)) = tasks.remove(origin) else {
+                continue;
+            };
+            state.origins.insert(task["id"].as_u64().unwrap_or_default(), origin.clone());
+            match deleted {
+                None => state.list.push(task),
+                Some(deleted) => state.trash.push(json!({ "task": task, "deleted": deleted })),
+            }
+        }
+        state
+    }
+
+    ///Reads the logs of every replica in a folder, lines that can't be read
+    ///(like a file still being copied) are skipped
+    pub fn read_dir(dir: &Path) -> Result<Self, TodoFileError> {
+        let mut log = OpLog::default();
+        if !dir.exists() {
+            return Ok(log);
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "jsonl") {
+                let ops = fs::read_to_string(&path)?.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+                log.merge(OpLog { ops });
+            }
+        }
+        Ok(log)
+    }
+}
+
+///Makes `Todo::load` and `Todo::save` use the operation log
+pub fn enable(settings: &OpLogSettings) {
+    if settings.enabled {
+        let _ = SETTINGS.set(settings.clone());
+    }
+}
+
+///Tasks rebuilt from the log when it is enabled and not empty
+pub fn load() -> Result<Option<State>, TodoFileError> {
+    match SETTINGS.get() {
+        Some(settings) => load_from(settings),
+        None => Ok(None),
+    }
+}
+
+///Appends the changes made to the list since it was loaded to the log, when it is enabled,
+///then updates the list with the changes of the other replicas. Returns false when the log is disabled
+pub fn record(list: &mut Todo) -> Result<bool, TodoFileError> {
+    match SETTINGS.get() {
+        Some(settings) => record_to(settings, list).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+fn load_from(settings: &OpLogSettings) -> Result<Option<State>, TodoFileError> {
+    let log = OpLog::read_dir(Path::new(&settings.dir))?;
+    Ok((!log.ops().is_empty()).then(|| log.replay()))
+}
+
+//Changes are found against the state the list was loaded from,
+//so changes made meanwhile by other replicas are not undone
+fn record_to(settings: &OpLogSettings, list: &mut Todo) -> Result<(), TodoFileError> {
+    let dir = Path::new(&settings.dir);
+    let replica = settings.replica_name();
+    let mut log = OpLog::read_dir(dir)?;
+    let empty = State::default();
+    let kinds = changes(list.log_state().unwrap_or(&empty), &log.replay(), &State::of(list)?);
+    if !kinds.is_empty() {
+        append(dir, replica, &mut log, kinds)?;
+    }
+    list.apply_log(log.replay())
+}
+
+//Writes the operations to the log of the replica
+fn append(dir: &Path, replica: &str, log: &mut OpLog, kinds: Vec<OpKind>) -> Result<(), TodoFileError> {
+
+    let clock = log.clock();
+    let seq = log.ops().iter().filter(|op| op.id.replica == replica).map(|op| op.id.seq).max().unwrap_or(0);
+    let at = Utc::now();
+    let mut ops = vec![];
+    let mut lines = String::new();
+    for (i, kind) in kinds.into_iter().enumerate() {
+        let i = i as u64 + 1;
+        let op = Op { id: OpId { replica: replica.to_string(), seq: seq + i }, clock: clock + i, at, kind };
+        lines.push_str(&serde_json::to_string(&op)?);
+        lines.push('\n');
+        ops.push(op);
+    }
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(dir.join(format!("{}.jsonl", replica)))?;
+    file.write_all(lines.as_bytes())?;
+    log.merge(OpLog { ops });
+    Ok(())
+}
+
+//Operations turning the baseline into the new list, skipping those already in the current log
+fn changes(baseline: &State, current: &State, new: &State) -> Vec<OpKind> {
+    let mut kinds = vec![];
+    for task in &new.list {
+        let id = task["id"].as_u64().unwrap_or_default();
+        let Some(origin) = baseline.origins.get(&id) else {
+            kinds.push(OpKind::Add { task: task.clone() });
+            continue;
+        };
+        let Some((before, trashed)) = baseline.get(id) else {
+            continue;
+        };
+        let now = current.find(origin);
+        if trashed && now.is_some_and(|(_, trashed)| trashed) {
+            kinds.push(OpKind::Restore { target: origin.clone() });
+        }
+        let fields: BTreeSet<&String> = before.as_object().into_iter().chain(task.as_object()).flat_map(|task| task.keys()).collect();
+        for field in fields.into_iter().filter(|field| *field != "id" && *field != "history") {
+            let value = task.get(field).cloned().unwrap_or_default();
+            if before.get(field) == Some(&value) || now.is_some_and(|(now, _)| now.get(field) == Some(&value)) {
+                continue;
+            }
+            let target = origin.clone();
+            kinds.push(match (field.as_str(), &value) {
+                ("name", Value::String(name)) => OpKind::Rename { target, name: name.clone() },
+                ("priority", Value::Number(priority)) => OpKind::SetPriority { target, priority: priority.as_u64().unwrap_or(0).min(10) as u8 },
+                ("done", Value::Bool(done)) => OpKind::Done { target, done: *done },
+                _ => OpKind::Set { target, field: field.clone(), value },
+            });
+        }
+        let history = |task: &Value| task["history"].as_array().cloned().unwrap_or_default();
+        let known = history(before);
+        let changes: Vec<Value> = history(task).into_iter().filter(|change| !known.contains(change)).collect();
+        if !changes.is_empty() {
+            kinds.push(OpKind::History { target: origin.clone(), changes });
+        }
+    }
+
+    for (id, origin) in &baseline.origins {
+        let Some((_, trashed)) = baseline.get(*id) else {
+            continue;
+        };
+        let now = current.find(origin);
+        let in_list = new.list.iter().any(|task| task["id"] == *id);
+        let in_trash = new.trash.iter().any(|trashed| trashed["task"]["id"] == *id);
+        if !trashed && !in_list && now.is_some_and(|(_, trashed)| !trashed) {
+            kinds.push(OpKind::Remove { target: origin.clone() });
+        }
+        if !in_list && !in_trash && now.is_some() {
+            kinds.push(OpKind::Purge { target: origin.clone() });
+        }
+    }
+    kinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(dir: &Path, replica: &str) -> OpLogSettings {
+        OpLogSettings { enabled: true, dir: dir.display().to_string(), replica: Some(replica.to_string()) }
+    }
+
+    //A list as loaded from the log
+    fn load(settings: &OpLogSettings) -> Todo {
+        let mut list = Todo::new();
+        if let Some(state) = load_from(settings).unwrap() {
+            list.apply_log(state).unwrap();
+        }
+        list
+    }
+
+    #[test]
+    fn concurrent_changes_are_merged() {
+        let dir = std::env::temp_dir().join(format!("todo-oplog-{}", std::process::id()));
+        let laptop = settings(&dir, "laptop");
+        let desktop = settings(&dir, "desktop");
+
+        let mut list = Todo::new();
+        list.add("Task1", 1);
+        record_to(&laptop, &mut list).unwrap();
+        //Saving again logs nothing
+        record_to(&laptop, &mut list).unwrap();
+        assert_eq!(OpLog::read_dir(&dir).unwrap().ops().len(), 1);
+
+        let mut on_laptop = load(&laptop);
+        let mut on_desktop = load(&desktop);
+        on_laptop.rename(0, "Renamed");
+        on_laptop.add("Laptop task", 2);
+        on_desktop.set_priority(0, 7);
+        on_desktop.add("Desktop task", 3);
+        on_desktop.set_done(0, true);
+        record_to(&laptop, &mut on_laptop).unwrap();
+        record_to(&desktop, &mut on_desktop).unwrap();
+        assert_eq!(on_desktop.items().len(), 3);
+
+        let list = load(&laptop);
+        let task = &list.items()[list.position(1).unwrap()];
+        assert_eq!((task.name.as_str(), task.priority, task.done), ("Renamed", 7, true));
+        //The histories of both replicas are kept
+        let changes: Vec<String> = task.history.iter().map(|change| change.kind.to_string()).collect();
+        assert_eq!(changes, vec!["created", "renamed from \"Task1\" to \"Renamed\"", "priority 1→7", "marked done"]);
+        let mut names: Vec<&str> = list.items().iter().map(|task| task.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Desktop task", "Laptop task", "Renamed"]);
+        let ids: BTreeSet<u64> = list.items().iter().map(|task| task.id).collect();
+        assert_eq!(ids.len(), 3);
+
+        let mut on_laptop = list;
+        let index = on_laptop.position(1).unwrap();
+        on_laptop.remove(&[index]).unwrap();
+        record_to(&laptop, &mut on_laptop).unwrap();
+        let list = load(&desktop);
+        assert_eq!(list.items().len(), 2);
+        assert_eq!(list.trash().len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn enabled_log_needs_a_replica_name() {
+        assert!(OpLogSettings::default().is_valid());
+        let mut settings = OpLogSettings { enabled: true, ..OpLogSettings::default() };
+        assert!(!settings.is_valid());
+        settings.replica = Some("laptop".to_string());
+        assert!(settings.is_valid());
+        settings.replica = Some("my laptop".to_string());
+        assert!(!settings.is_valid());
+    }
+
+    #[test]
+    fn merge_is_deterministic() {
+        let at = Utc::now();
+        let op = |replica: &str, seq: u64, clock: u64, kind: OpKind| Op { id: OpId { replica: replica.to_string(), seq }, clock, at, kind };
+        let origin = OpId { replica: "a".to_string(), seq: 1 };
+        let a = OpLog {
+            ops: vec![
+                op("a", 1, 1, OpKind::Add { task: json!({ "id": 1, "name": "Task1", "priority": 1, "done": false }) }),
+                op("a", 2, 2, OpKind::Rename { target: origin.clone(), name: "From a".to_string() }),
+            ],
+        };
+        let b = OpLog {
+            ops: vec![
+                op("a", 1, 1, a.ops[0].kind.clone()),
+                op("b", 1, 2, OpKind::Rename { target: origin.clone(), name: "From b".to_string() }),
+                op("b", 2, 3, OpKind::Add { task: json!({ "id": 1, "name": "Task2", "priority": 1, "done": false }) }),
+            ],
+        };
+        let mut ab = a.clone();
+        ab.merge(b.clone());
+        let mut ba = b;
+        ba.merge(a);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.ops().len(), 4);
+
+        let state = ab.replay();
+        assert_eq!(state.list[0]["name"], "From b");
+        assert_eq!(state.list[1]["id"], 2);
+        assert_eq!(state.origins[&1], origin);
+    }
+}
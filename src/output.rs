@@ -0,0 +1,177 @@
+use clap::ValueEnum;
+use std::io::{self, Write};
+
+use crate::todo::Task;
+
+///Output formats of `todo ls`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    ///Colored lines for humans
+    #[default]
+    Text,
+    ///One JSON array
+    Json,
+    ///One JSON object per line
+    Jsonl,
+    ///Tab separated values with a header line
+    Tsv,
+}
+
+///Fields available in TSV and templates
+pub const FIELDS: [&str; 10] = ["id", "name", "priority", "done", "due", "tags", "assignee", "state", "created", "completed"];
+
+///Writes the tasks in the given format
+pub fn write(out: &mut impl Write, tasks: &[&Task], format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => {
+            if tasks.is_empty() {
+                writeln!(out, "[Empty list]")?;
+            }
+            for task in tasks {
+                writeln!(out, "{} - {} [{}]", task.id, task.to_formated_string(), task.priority)?;
+            }
+        }
+        Format::Json => writeln!(out, "{}", serde_json::to_string(tasks)?)?,
+        Format::Jsonl => {
+            for task in tasks {
+                writeln!(out, "{}", serde_json::to_string(task)?)?;
+            }
+        }
+        Format::Tsv => {
+            writeln!(out, "{}", FIELDS.join("\t"))?;
+            for task in tasks {
+                let values: Vec<String> = FIELDS.iter().map(|field| field_value(task, field).unwrap_or_default()).collect();
+                writeln!(out, "{}", values.join("\t"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+///Template with `{field}` placeholders, `{{` and `}}` write braces, `\t` and `\n` are tabs and new lines
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let template = template.replace("\\t", "\t").replace("\\n", "\n");
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("Unterminated field {{{}, a '}}' is missing", field)),
+                        }
+                    }
+                    if !FIELDS.contains(&field.as_str()) {
+                        return Err(format!("Unknown field {{{}}}, available: {}", field, FIELDS.join(", ")));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, task: &Task) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field_value(task, field).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn write(&self, out: &mut impl Write, tasks: &[&Task]) -> io::Result<()> {
+        for task in tasks {
+            writeln!(out, "{}", self.render(task))?;
+        }
+        Ok(())
+    }
+}
+
+//Value of a field on one line, tabs and new lines in the text are replaced by spaces
+fn field_value(task: &Task, field: &str) -> Option<String> {
+    let value = match field {
+        "id" => task.id.to_string(),
+        "name" => task.name.clone(),
+        "priority" => task.priority.to_string(),
+        "done" => task.done.to_string(),
+        "due" => task.due?.to_string(),
+        "tags" => task.tags.join(","),
+        "assignee" => task.assignee.clone()?,
+        "state" => task.state.clone()?,
+        "created" => task.created?.to_rfc3339(),
+        "completed" => task.completed?.to_rfc3339(),
+        _ => return None,
+    };
+    Some(value.replace(['\t', '\n', '\r'], " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> Task {
+        let mut task = Task::new("Fix\tbug", 7);
+        task.id = 3;
+        task.tags = vec!["api".to_string(), "ops\tteam".to_string()];
+        task
+    }
+
+    #[test]
+    fn write_formats() {
+        let task = task();
+        let mut out = vec![];
+        write(&mut out, &[&task, &task], Format::Jsonl).unwrap();
+        let lines: Vec<serde_json::Value> =
+            String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["name"], "Fix\tbug");
+
+        let mut out = vec![];
+        write(&mut out, &[&task], Format::Tsv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let row: Vec<&str> = out.lines().nth(1).unwrap().split('\t').collect();
+        assert_eq!(row.len(), FIELDS.len());
+        assert_eq!(&row[..6], ["3", "Fix bug", "7", "false", "", "api,ops team"]);
+    }
+
+    #[test]
+    fn templates() {
+        let template = Template::parse("{{{id}}}\\t{name} !{priority} {due}").unwrap();
+        assert_eq!(template.render(&task()), "{3}\tFix bug !7 ");
+        assert!(Template::parse("{colour}").is_err());
+        assert!(Template::parse("{name").is_err());
+    }
+}
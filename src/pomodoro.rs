@@ -0,0 +1,143 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+use crate::notify;
+
+///Lengths of the focus cycles and how the end of a cycle is announced
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PomodoroSettings {
+    pub work_minutes: u32,
+    pub break_minutes: u32,
+    ///Rings the terminal bell at the end of each cycle
+    pub bell: bool,
+    ///Shell command run at the end of each cycle, the message is in $TODO_MESSAGE
+    pub notify_command: Option<String>,
+}
+
+impl Default for PomodoroSettings {
+    fn default() -> Self {
+        PomodoroSettings {
+            work_minutes: 25,
+            break_minutes: 5,
+            bell: true,
+            notify_command: None,
+        }
+    }
+}
+
+impl PomodoroSettings {
+    pub fn is_valid(&self) -> bool {
+        self.work_minutes > 0 && self.break_minutes > 0
+    }
+
+    ///Announces the end of a cycle, failures are ignored to keep the interface running
+    pub fn notify(&self, message: &str) {
+        if self.bell {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+        if let Some(command) = &self.notify_command {
+            notify::run_detached(notify::command(command, message));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+///Focus timer bound to a task, alternating work and break cycles
+#[derive(Debug, Clone)]
+pub struct Pomodoro {
+    task_id: u64,
+    phase: Phase,
+    started: DateTime<Utc>,
+    settings: PomodoroSettings,
+}
+
+impl Pomodoro {
+    pub fn new(task_id: u64, settings: PomodoroSettings, now: DateTime<Utc>) -> Self {
+        Pomodoro {
+            task_id,
+            phase: Phase::Work,
+            started: now,
+            settings,
+        }
+    }
+
+    pub fn task_id(&self) -> u64 {
+        self.task_id
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn settings(&self) -> &PomodoroSettings {
+        &self.settings
+    }
+
+    ///Length of the current cycle
+    pub fn length(&self) -> TimeDelta {
+        let minutes = match self.phase {
+            Phase::Work => self.settings.work_minutes,
+            Phase::Break => self.settings.break_minutes,
+        };
+        TimeDelta::minutes(minutes as i64)
+    }
+
+    pub fn remaining(&self, now: DateTime<Utc>) -> TimeDelta {
+        (self.length() - (now - self.started)).max(TimeDelta::zero())
+    }
+
+    ///Part of the current cycle already spent, between 0 and 1
+    pub fn ratio(&self, now: DateTime<Utc>) -> f64 {
+        let length = self.length().num_seconds().max(1) as f64;
+        (1.0 - self.remaining(now).num_seconds() as f64 / length).clamp(0.0, 1.0)
+    }
+
+    ///Starts the next cycle once the current one is over, returns the finished phase
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Option<Phase> {
+        if self.remaining(now) > TimeDelta::zero() {
+            return None;
+        }
+        let finished = self.phase;
+        self.started += self.length();
+        self.phase = match finished {
+            Phase::Work => Phase::Break,
+            Phase::Break => Phase::Work,
+        };
+        Some(finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_alternate() {
+        let start = Utc::now();
+        let mut pomodoro = Pomodoro::new(1, PomodoroSettings::default(), start);
+        assert_eq!(pomodoro.tick(start + TimeDelta::minutes(10)), None);
+        assert_eq!(pomodoro.remaining(start + TimeDelta::minutes(10)), TimeDelta::minutes(15));
+        assert!((pomodoro.ratio(start + TimeDelta::minutes(10)) - 0.4).abs() < 0.01);
+
+        assert_eq!(pomodoro.tick(start + TimeDelta::minutes(25)), Some(Phase::Work));
+        assert_eq!(pomodoro.phase(), Phase::Break);
+        assert_eq!(pomodoro.remaining(start + TimeDelta::minutes(26)), TimeDelta::minutes(4));
+        assert_eq!(pomodoro.tick(start + TimeDelta::minutes(30)), Some(Phase::Break));
+        assert_eq!(pomodoro.phase(), Phase::Work);
+    }
+
+    #[test]
+    fn zero_length_is_invalid() {
+        let settings = PomodoroSettings { work_minutes: 0, ..Default::default() };
+        assert!(!settings.is_valid());
+        assert!(PomodoroSettings::default().is_valid());
+    }
+}
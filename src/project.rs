@@ -0,0 +1,137 @@
+use std::env;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::todo::{Todo, TodoFileError};
+
+///Name of the list file of a project
+pub const FILE: &str = ".todo";
+
+///Depth of the folders searched for project lists, below each search folder
+const SEARCH_DEPTH: usize = 4;
+
+///List of the directory or of its closest parent having one, like git finds its repository
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(FILE)).find(|path| path.is_file())
+}
+
+///List used outside of projects: $XDG_DATA_HOME/todo/tasks.json, or ~/.local/share/todo/tasks.json
+pub fn global_path() -> Option<PathBuf> {
+    let data = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))?;
+    Some(data.join("todo").join("tasks.json"))
+}
+
+///Lists opened together by `todo --all`: the lists of the directory and of its parents, closest first,
+///the project lists found in the search folders, then the tasks.json of older versions and the global list if they exist
+pub fn discover(dir: &Path, search: &[String]) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = dir.ancestors().map(|dir| dir.join(FILE)).filter(|path| path.is_file()).collect();
+    for folder in search {
+        let mut lists = vec![];
+        scan(&expand_home(folder), SEARCH_DEPTH, &mut lists);
+        lists.sort();
+        found.extend(lists);
+    }
+    let legacy = Path::new(Todo::DEFAULT_PATH).file_name().map(|name| dir.join(name));
+    found.extend(legacy.into_iter().chain(global_path()).filter(|path| path.is_file()));
+    //The same list can be reached from several places
+    let mut seen = vec![];
+    found.retain(|path| {
+        let canonical = path.canonicalize().unwrap_or(path.clone());
+        let new = !seen.contains(&canonical);
+        seen.push(canonical);
+        new
+    });
+    found
+}
+
+///Creates an empty project list in the directory, fails if it already has one
+pub fn init(dir: &Path) -> Result<PathBuf, TodoFileError> {
+    let path = dir.join(FILE);
+    if path.exists() {
+        return Err(io::Error::new(ErrorKind::AlreadyExists, format!("{} already exists", path.display())).into());
+    }
+    Todo::new().save_to(path.to_string_lossy().into_owned())?;
+    Ok(path)
+}
+
+///Short name of a list for the interface: the global list, or the path of the file with ~ for the home directory
+pub fn label(path: &str) -> String {
+    if global_path().is_some_and(|global| Path::new(path) == global) {
+        return "global list".to_string();
+    }
+    let home = env::var_os("HOME").map(PathBuf::from);
+    match home.as_deref().and_then(|home| Path::new(path).strip_prefix(home).ok()) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.to_string(),
+    }
+}
+
+//Adds the project lists of the folder and of its subfolders, hidden folders are skipped
+fn scan(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let list = dir.join(FILE);
+    if list.is_file() {
+        found.push(list);
+    }
+    let Some(entries) = dir.read_dir().ok().filter(|_| depth > 0) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            scan(&entry.path(), depth - 1, found);
+        }
+    }
+}
+
+//Replaces a leading ~ by the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn lists_are_found_in_parent_directories() {
+        let root = env::temp_dir().join(format!("todo-project-{}", std::process::id()));
+        let nested = root.join("app").join("src").join("ui");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find(&nested), None);
+
+        let app = init(&root.join("app")).unwrap();
+        assert_eq!(app, root.join("app").join(FILE));
+        assert_eq!(find(&nested), Some(app.clone()));
+        assert!(init(&root.join("app")).is_err());
+        //The closest list wins
+        let ui = init(&nested).unwrap();
+        assert_eq!(find(&nested), Some(ui));
+        assert_eq!(find(&root.join("app").join("src")), Some(app));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn lists_are_discovered_in_search_folders() {
+        let root = env::temp_dir().join(format!("todo-discover-{}", std::process::id()));
+        for dir in ["api", "web/front", ".cache/old", "web/front/src"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for dir in ["api", "web/front", ".cache/old"] {
+            init(&root.join(dir)).unwrap();
+        }
+        let legacy = root.join("web/front/src/tasks.json");
+        Todo::new().save_to(legacy.display().to_string()).unwrap();
+        let search = vec![root.display().to_string()];
+        let found: Vec<PathBuf> = discover(&root.join("web/front/src"), &search).into_iter().filter(|path| path.starts_with(&root)).collect();
+        //The list of the current project comes first and is not repeated
+        assert_eq!(found, vec![root.join("web/front").join(FILE), root.join("api").join(FILE), legacy]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
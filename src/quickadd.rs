@@ -0,0 +1,174 @@
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+
+use crate::todo::{Recurrence, Task};
+
+///Fields read from a task written with the inline syntax:
+///`Fix login bug !7 #backend @bob due:fri every:week`
+#[derive(Debug, Default, PartialEq)]
+pub struct QuickAdd {
+    pub name: String,
+    pub priority: Option<u8>,
+    pub tags: Vec<String>,
+    pub assignee: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl QuickAdd {
+    ///Reads the inline fields, the words that are not understood stay in the name
+    pub fn parse(text: &str, today: NaiveDate) -> Self {
+        let mut parsed = QuickAdd::default();
+        let mut name = vec![];
+        for word in text.split_whitespace() {
+            if !parsed.read_field(word, today) {
+                name.push(word);
+            }
+        }
+        parsed.name = name.join(" ");
+        parsed
+    }
+
+    ///Short description of the fields, like "!7 #backend @bob due 2024-03-01 every week"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        if let Some(priority) = self.priority {
+            parts.push(format!("!{}", priority));
+        }
+        parts.extend(self.tags.iter().map(|tag| format!("#{}", tag)));
+        if let Some(assignee) = &self.assignee {
+            parts.push(format!("@{}", assignee));
+        }
+        if let Some(due) = self.due {
+            parts.push(format!("due {}", due));
+        }
+        if let Some(recurrence) = self.recurrence {
+            parts.push(recurrence.label().to_string());
+        }
+        parts.join(" ")
+    }
+
+    ///Builds the task, the priority is used when none was written
+    pub fn to_task(&self, priority: u8) -> Task {
+        let mut task = Task::new(&self.name, self.priority.unwrap_or(priority));
+        self.apply(&mut task);
+        task
+    }
+
+    ///Sets the fields that were written on an existing task, tags are added to its tags
+    pub fn apply(&self, task: &mut Task) {
+        task.name = self.name.clone();
+        if let Some(priority) = self.priority {
+            task.priority = priority;
+        }
+        for tag in &self.tags {
+            if !task.tags.contains(tag) {
+                task.tags.push(tag.clone());
+            }
+        }
+        if self.assignee.is_some() {
+            task.assignee = self.assignee.clone();
+        }
+        if self.due.is_some() {
+            task.due = self.due;
+        }
+        if self.recurrence.is_some() {
+            task.recurrence = self.recurrence;
+        }
+    }
+
+    //Returns true if the word was an inline field
+    fn read_field(&mut self, word: &str, today: NaiveDate) -> bool {
+        if let Some(priority) = word.strip_prefix('!') {
+            match priority.parse::<u8>() {
+                Ok(priority) if priority <= 10 => self.priority = Some(priority),
+                _ => return false,
+            }
+        } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            if !self.tags.iter().any(|t| t == tag) {
+                self.tags.push(tag.to_string());
+            }
+        } else if let Some(assignee) = word.strip_prefix('@').filter(|name| !name.is_empty()) {
+            self.assignee = Some(assignee.to_string());
+        } else if let Some(due) = word.strip_prefix("due:") {
+            match parse_date(due, today) {
+                Some(due) => self.due = Some(due),
+                None => return false,
+            }
+        } else if let Some(every) = word.strip_prefix("every:") {
+            match Recurrence::parse(every) {
+                Some(recurrence) => self.recurrence = Some(recurrence),
+                None => return false,
+            }
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+///Reads a date like "2024-03-01", "today", "tomorrow", "fri", "3d" or "2w".
+///A weekday is the next one, today included
+pub fn parse_date(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let text = text.to_lowercase();
+    match text.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return Some(today + TimeDelta::days(1)),
+        _ => (),
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return Some(date);
+    }
+    if let Ok(weekday) = text.parse::<Weekday>() {
+        let days = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+        return Some(today + TimeDelta::days(days as i64));
+    }
+    if let Some(days) = text.strip_suffix('d') {
+        return today.checked_add_signed(TimeDelta::try_days(days.parse().ok()?)?);
+    }
+    let weeks = text.strip_suffix('w')?;
+    today.checked_add_signed(TimeDelta::try_weeks(weeks.parse().ok()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_inline_fields() {
+        //2024-03-04 is a monday
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let parsed = QuickAdd::parse("Fix login bug !7 #backend @bob due:fri every:week", today);
+        assert_eq!(
+            parsed,
+            QuickAdd {
+                name: "Fix login bug".to_string(),
+                priority: Some(7),
+                tags: vec!["backend".to_string()],
+                assignee: Some("bob".to_string()),
+                due: NaiveDate::from_ymd_opt(2024, 3, 8),
+                recurrence: Some(Recurrence::Weekly),
+            }
+        );
+        assert_eq!(parsed.summary(), "!7 #backend @bob due 2024-03-08 every week");
+    }
+
+    #[test]
+    fn unknown_fields_stay_in_name() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let parsed = QuickAdd::parse("Say hi! !42 due:someday every:now", today);
+        assert_eq!(parsed.name, "Say hi! !42 due:someday every:now");
+        assert_eq!(parsed.summary(), "");
+    }
+
+    #[test]
+    fn parse_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        assert_eq!(parse_date("mon", today), Some(today));
+        assert_eq!(parse_date("Sunday", today), NaiveDate::from_ymd_opt(2024, 3, 10));
+        assert_eq!(parse_date("tomorrow", today), NaiveDate::from_ymd_opt(2024, 3, 5));
+        assert_eq!(parse_date("2w", today), NaiveDate::from_ymd_opt(2024, 3, 18));
+        assert_eq!(parse_date("2024-12-25", today), NaiveDate::from_ymd_opt(2024, 12, 25));
+        assert_eq!(parse_date("x", today), None);
+        assert_eq!(parse_date("99999999999d", today), None);
+    }
+}
@@ -0,0 +1,328 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::ConfigError;
+use crate::notify;
+use crate::todo::{Task, Todo};
+
+//==== Settings
+
+///How reminders are delivered and how often the tasks are checked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReminderSettings {
+    pub notifier: NotifierKind,
+    pub interval_seconds: u64,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        ReminderSettings {
+            notifier: NotifierKind::Stdout,
+            interval_seconds: 30,
+        }
+    }
+}
+
+impl ReminderSettings {
+    pub fn is_valid(&self) -> bool {
+        self.interval_seconds > 0
+    }
+}
+
+///Notifier chosen in the config: "stdout", "desktop" or {"command": "..."}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    Stdout,
+    Desktop,
+    Command(String),
+}
+
+impl NotifierKind {
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self {
+            NotifierKind::Stdout => Box::new(StdoutNotifier),
+            NotifierKind::Desktop => Box::new(DesktopNotifier),
+            NotifierKind::Command(command) => Box::new(CommandNotifier(command.clone())),
+        }
+    }
+}
+
+//==== Notifiers
+
+///Delivers a reminder to the user
+pub trait Notifier {
+    fn notify(&self, reminder: &Reminder) -> io::Result<()>;
+}
+
+///Prints reminders on the terminal
+pub struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    fn notify(&self, reminder: &Reminder) -> io::Result<()> {
+        println!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M"), reminder.message());
+        Ok(())
+    }
+}
+
+///Shows reminders as desktop notifications through notify-send
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, reminder: &Reminder) -> io::Result<()> {
+        run(Command::new("notify-send").arg("Todo").arg(reminder.message()))
+    }
+}
+
+///Runs a shell command, the reminder is given in $TODO_MESSAGE and $TODO_TASK_ID
+pub struct CommandNotifier(pub String);
+
+impl CommandNotifier {
+    pub const TASK_ID_VAR: &str = "TODO_TASK_ID";
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, reminder: &Reminder) -> io::Result<()> {
+        run(notify::command(&self.0, &reminder.message()).env(Self::TASK_ID_VAR, reminder.task_id.to_string()))
+    }
+}
+
+//Runs a notification command, a failing command is an error
+fn run(command: &mut Command) -> io::Result<()> {
+    let status = command.stdin(Stdio::null()).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("notifier exited with {}", status)))
+    }
+}
+
+//==== Reminder
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderKind {
+    ///The due day of the task has started
+    Due,
+    ///The reminder time set on the task has passed
+    At,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub task_id: u64,
+    pub name: String,
+    pub kind: ReminderKind,
+    pub at: DateTime<Utc>,
+}
+
+impl Reminder {
+    pub fn message(&self) -> String {
+        match self.kind {
+            ReminderKind::Due => format!("\"{}\" is due on {}", self.name, self.at.with_timezone(&Local).date_naive()),
+            ReminderKind::At => format!("Reminder: \"{}\"", self.name),
+        }
+    }
+
+    ///Identifies a delivery, changing the date of a task gives a new reminder
+    fn key(&self) -> String {
+        let kind = match self.kind {
+            ReminderKind::Due => "due",
+            ReminderKind::At => "at",
+        };
+        format!("{}:{}:{}", self.task_id, kind, self.at.to_rfc3339())
+    }
+
+    ///Reminders of a task, whether they are past or not
+    fn of(task: &Task) -> Vec<Reminder> {
+        let mut reminders = vec![];
+        if task.done {
+            return reminders;
+        }
+        if let Some(due) = task.due {
+            let midnight = due.and_hms_opt(0, 0, 0).unwrap();
+            if let Some(at) = Local.from_local_datetime(&midnight).earliest() {
+                reminders.push(Reminder {
+                    task_id: task.id,
+                    name: task.name.clone(),
+                    kind: ReminderKind::Due,
+                    at: at.with_timezone(&Utc),
+                });
+            }
+        }
+        if let Some(at) = task.remind_at {
+            reminders.push(Reminder {
+                task_id: task.id,
+                name: task.name.clone(),
+                kind: ReminderKind::At,
+                at,
+            });
+        }
+        reminders
+    }
+}
+
+//==== Delivered
+
+///Reminders already delivered, saved so a restart does not fire them again
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Delivered {
+    keys: BTreeSet<String>,
+}
+
+impl Delivered {
+    ///Added to the path of the list to get the path of its delivered reminders
+    pub const SUFFIX: &str = ".reminders.json";
+    pub const PATH_VAR: &str = "TODO_REMINDERS";
+
+    ///$TODO_REMINDERS, else a file next to the list, so each list keeps its own record
+    ///wherever todo is started from
+    pub fn load_path(list_path: &str) -> String {
+        match env::var(Self::PATH_VAR) {
+            Ok(val) => val,
+            Err(_e) => format!("{}{}", list_path, Self::SUFFIX),
+        }
+    }
+
+    ///Reads the delivered reminders, a missing file means none were delivered
+    pub fn read_from_file(path: &str) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(buff) => Ok(serde_json::from_str(&buff)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Delivered::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_to(&self, path: &str) -> Result<(), ConfigError> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn contains(&self, reminder: &Reminder) -> bool {
+        self.keys.contains(&reminder.key())
+    }
+
+    pub fn insert(&mut self, reminder: &Reminder) {
+        self.keys.insert(reminder.key());
+    }
+}
+
+//==== Watching
+
+///Past reminders of the open tasks that were not delivered yet
+pub fn pending(todo: &Todo, delivered: &Delivered, now: DateTime<Utc>) -> Vec<Reminder> {
+    todo.items()
+        .iter()
+        .flat_map(Reminder::of)
+        .filter(|reminder| reminder.at <= now && !delivered.contains(reminder))
+        .collect()
+}
+
+///Delivers the pending reminders, a failed one is retried at the next check.
+///Returns true if a reminder was delivered
+pub fn check(todo: &Todo, delivered: &mut Delivered, notifier: &dyn Notifier, now: DateTime<Utc>) -> bool {
+    let mut changed = false;
+    for reminder in pending(todo, delivered, now) {
+        match notifier.notify(&reminder) {
+            Ok(()) => {
+                delivered.insert(&reminder);
+                changed = true;
+            }
+            Err(e) => eprintln!("Could not deliver \"{}\" : {}", reminder.message(), e),
+        }
+    }
+    changed
+}
+
+///Checks the task file at each interval, the file is read again each time to see the changes
+pub fn watch(settings: &ReminderSettings, once: bool) -> Result<(), ConfigError> {
+    let path = Delivered::load_path(&Todo::load_path());
+    let mut delivered = Delivered::read_from_file(&path)?;
+    let notifier = settings.notifier.build();
+    loop {
+        match Todo::load() {
+            Ok(todo) => {
+                if check(&todo, &mut delivered, notifier.as_ref(), Utc::now()) {
+                    delivered.save_to(&path)?;
+                }
+            }
+            Err(e) => eprintln!("Could not read {} : {}", Todo::load_path(), e),
+        }
+        if once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(settings.interval_seconds));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeDelta};
+    use std::cell::RefCell;
+
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, reminder: &Reminder) -> io::Result<()> {
+            self.0.borrow_mut().push(reminder.message());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reminders_fire_once() {
+        let mut todo = Todo::new();
+        todo.add("Report", 1);
+        todo.add("Call", 1);
+        todo.add("Later", 1);
+        let now = Utc::now();
+        todo.set_due(0, NaiveDate::from_ymd_opt(2024, 1, 1));
+        todo.set_reminder(1, Some(now - TimeDelta::minutes(1)));
+        todo.set_reminder(2, Some(now + TimeDelta::hours(1)));
+
+        let recorder = Recorder(RefCell::new(vec![]));
+        let mut delivered = Delivered::default();
+        assert!(check(&todo, &mut delivered, &recorder, now));
+        assert_eq!(recorder.0.borrow().len(), 2);
+
+        //Saved deliveries are not fired again after a restart
+        let path = "./test_reminders.json";
+        assert_eq!(Delivered::load_path("/data/.todo"), "/data/.todo.reminders.json");
+        delivered.save_to(path).unwrap();
+        let mut delivered = Delivered::read_from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(!check(&todo, &mut delivered, &recorder, now));
+        assert_eq!(recorder.0.borrow().len(), 2);
+
+        assert!(check(&todo, &mut delivered, &recorder, now + TimeDelta::hours(2)));
+        assert_eq!(recorder.0.borrow().last().unwrap(), "Reminder: \"Later\"");
+    }
+
+    #[test]
+    fn done_tasks_are_not_reminded() {
+        let mut todo = Todo::new();
+        todo.add("Report", 1);
+        todo.set_due(0, NaiveDate::from_ymd_opt(2024, 1, 1));
+        todo.set_done(0, true);
+        assert!(pending(&todo, &Delivered::default(), Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn notifier_kinds_from_config() {
+        let kinds: Vec<NotifierKind> =
+            serde_json::from_str(r#"["stdout", "desktop", {"command": "echo hi"}]"#).unwrap();
+        assert_eq!(
+            kinds,
+            vec![NotifierKind::Stdout, NotifierKind::Desktop, NotifierKind::Command("echo hi".to_string())]
+        );
+    }
+}
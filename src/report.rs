@@ -0,0 +1,127 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use std::collections::BTreeMap;
+
+use crate::stats::format_duration;
+use crate::todo::Todo;
+
+///Time spent per task, tag and day
+#[derive(Debug, PartialEq)]
+pub struct TimeReport {
+    pub by_task: Vec<(String, TimeDelta)>,
+    pub by_tag: Vec<(String, TimeDelta)>,
+    pub by_day: Vec<(NaiveDate, TimeDelta)>,
+}
+
+impl TimeReport {
+    pub const NO_TAG: &str = "(no tag)";
+
+    pub fn compute(todo: &Todo, now: DateTime<Utc>) -> Self {
+        let mut by_task = vec![];
+        let mut by_tag: BTreeMap<String, TimeDelta> = BTreeMap::new();
+        let mut by_day: BTreeMap<NaiveDate, TimeDelta> = BTreeMap::new();
+
+        for task in todo.items() {
+            let total = task.tracked(now);
+            if task.time_entries.is_empty() {
+                continue;
+            }
+            by_task.push((task.name.clone(), total));
+            if task.tags.is_empty() {
+                *by_tag.entry(Self::NO_TAG.to_string()).or_default() += total;
+            }
+            for tag in &task.tags {
+                *by_tag.entry(tag.clone()).or_default() += total;
+            }
+            for entry in &task.time_entries {
+                let start = entry.start.with_timezone(&Local).naive_local();
+                let end = entry.end.unwrap_or(now).with_timezone(&Local).naive_local();
+                for (day, duration) in split_by_day(start, end) {
+                    *by_day.entry(day).or_default() += duration;
+                }
+            }
+        }
+        by_task.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+
+        TimeReport {
+            by_task,
+            by_tag: by_tag.into_iter().collect(),
+            by_day: by_day.into_iter().collect(),
+        }
+    }
+
+    ///Prints the report on the terminal
+    pub fn print(&self) {
+        if self.by_task.is_empty() {
+            println!("[No time tracked]");
+            return;
+        }
+        println!("Time per task");
+        for (name, duration) in &self.by_task {
+            println!("  {:<40} {}", name, format_duration(*duration));
+        }
+        println!("Time per tag");
+        for (tag, duration) in &self.by_tag {
+            println!("  {:<40} {}", tag, format_duration(*duration));
+        }
+        println!("Time per day");
+        for (day, duration) in &self.by_day {
+            println!("  {:<40} {}", day, format_duration(*duration));
+        }
+    }
+}
+
+//Cuts a period at each midnight
+fn split_by_day(start: NaiveDateTime, end: NaiveDateTime) -> Vec<(NaiveDate, TimeDelta)> {
+    let mut parts = vec![];
+    let mut current = start;
+    while current < end {
+        let day = current.date();
+        let next_day = (day + TimeDelta::days(1)).and_hms_opt(0, 0, 0).unwrap();
+        let part_end = next_day.min(end);
+        parts.push((day, part_end - current));
+        current = part_end;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_period_at_midnight() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(23, 0, 0).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap().and_hms_opt(1, 30, 0).unwrap();
+        assert_eq!(
+            split_by_day(start, end),
+            vec![
+                (start.date(), TimeDelta::hours(1)),
+                (end.date(), TimeDelta::minutes(90)),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_by_task_and_tag() {
+        let todo: Todo = serde_json::from_str(
+            r#"{"list": [
+                {"name": "Short", "priority": 1, "done": false, "tags": ["backend"],
+                 "time_entries": [{"start": "2024-03-01T10:00:00Z", "end": "2024-03-01T10:30:00Z"}]},
+                {"name": "Long", "priority": 1, "done": false, "tags": ["backend", "api"],
+                 "time_entries": [{"start": "2024-03-01T11:00:00Z", "end": "2024-03-01T13:00:00Z"}]},
+                {"name": "Untracked", "priority": 1, "done": false}
+            ]}"#,
+        )
+        .unwrap();
+        let report = TimeReport::compute(&todo, Utc::now());
+        assert_eq!(
+            report.by_task,
+            vec![("Long".to_string(), TimeDelta::hours(2)), ("Short".to_string(), TimeDelta::minutes(30))]
+        );
+        assert_eq!(
+            report.by_tag,
+            vec![("api".to_string(), TimeDelta::hours(2)), ("backend".to_string(), TimeDelta::minutes(150))]
+        );
+        assert_eq!(report.by_day.iter().map(|d| d.1).sum::<TimeDelta>(), TimeDelta::minutes(150));
+    }
+}
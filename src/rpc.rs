@@ -0,0 +1,295 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::todo::Todo;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+///Time between two checks of the list file
+const POLL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    ///Requests without id are notifications and get no response, a null id still gets one
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct AddParams {
+    name: String,
+    #[serde(default)]
+    priority: u8,
+}
+
+#[derive(Deserialize)]
+struct DoneParams {
+    id: u64,
+    ///Toggles the task when missing
+    done: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct RenameParams {
+    id: u64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PriorityParams {
+    id: u64,
+    priority: u8,
+}
+
+#[derive(Deserialize)]
+struct RemoveParams {
+    ids: Vec<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+}
+
+///Answer to one line of input
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    ///None for notifications
+    pub response: Option<Value>,
+    ///The list was modified and must be saved
+    pub changed: bool,
+}
+
+///Runs one line of JSON-RPC on the list. Methods take task ids:
+///`add {name, priority}`, `done {id, done?}`, `rename {id, name}`,
+///`set_priority {id, priority}`, `remove {ids}` and `items`
+pub fn handle(list: &mut Todo, line: &str) -> Outcome {
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Err(e) => return error_outcome(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => return error_outcome(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string())),
+        },
+    };
+    let id = request.id.clone();
+    if request.jsonrpc != "2.0" {
+        return error_outcome(id.unwrap_or_default(), RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    let (result, changed) = match call(list, &request.method, request.params) {
+        Ok((result, changed)) => (Ok(result), changed),
+        Err(e) => (Err(e), false),
+    };
+    let response = id.map(|id| match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    });
+    Outcome { response, changed }
+}
+
+fn call(list: &mut Todo, method: &str, params: Value) -> Result<(Value, bool), RpcError> {
+    match method {
+        "items" => Ok((json!(list.items()), false)),
+        "add" => {
+            let params: AddParams = parse(params)?;
+            if params.name.trim().is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "The task needs a name"));
+            }
+            list.add(&params.name, params.priority);
+            let index = list.items().len() - 1;
+            Ok((json!(list.task(index)), true))
+        }
+        "done" => {
+            let params: DoneParams = parse(params)?;
+            let index = find(list, params.id)?;
+            let done = params.done.unwrap_or(!list.task(index).done);
+            list.set_done(index, done);
+            Ok((task(list, params.id)?, true))
+        }
+        "rename" => {
+            let params: RenameParams = parse(params)?;
+            if params.name.trim().is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "The task needs a name"));
+            }
+            let index = find(list, params.id)?;
+            list.rename(index, &params.name);
+            Ok((task(list, params.id)?, true))
+        }
+        "set_priority" => {
+            let params: PriorityParams = parse(params)?;
+            let index = find(list, params.id)?;
+            list.set_priority(index, params.priority.min(10));
+            Ok((task(list, params.id)?, true))
+        }
+        "remove" => {
+            let params: RemoveParams = parse(params)?;
+            let indexes = params.ids.iter().map(|id| find(list, *id)).collect::<Result<Vec<_>, _>>()?;
+            list.remove(&indexes).map_err(|_| RpcError::new(INTERNAL_ERROR, "Could not remove the tasks"))?;
+            Ok((Value::Null, true))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+    }
+}
+
+//Keeps a member that is present but null, serde reads it as missing otherwise
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn find(list: &Todo, id: u64) -> Result<usize, RpcError> {
+    list.position(id).ok_or(RpcError::new(INVALID_PARAMS, format!("No task with id {}", id)))
+}
+
+fn task(list: &Todo, id: u64) -> Result<Value, RpcError> {
+    Ok(json!(list.task(find(list, id)?)))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": error.code, "message": error.message } })
+}
+
+fn error_outcome(id: Value, error: RpcError) -> Outcome {
+    Outcome { response: Some(error_response(id, error)), changed: false }
+}
+
+//==== Stdio loop
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+//Writes one message per line
+fn send(message: &Value) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", message);
+    let _ = stdout.flush();
+}
+
+///Reads requests on stdin until it is closed. The list file is read again for each request,
+///and a `changed` notification with the tasks is sent when another program modifies it
+pub fn run() -> io::Result<()> {
+    let path = Todo::load_path();
+    //Last modification time of the file seen, the lock also keeps requests and notifications apart
+    let last_modified = Arc::new(Mutex::new(modified(&path)));
+
+    let watched = Arc::clone(&last_modified);
+    let watched_path = path.clone();
+    thread::spawn(move || loop {
+        thread::sleep(POLL);
+        let mut last = watched.lock().unwrap();
+        let current = modified(&watched_path);
+        if current == *last {
+            continue;
+        }
+        *last = current;
+        if let Ok(list) = Todo::load() {
+            send(&json!({ "jsonrpc": "2.0", "method": "changed", "params": { "items": list.items() } }));
+        }
+    });
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut last = last_modified.lock().unwrap();
+        let outcome = match Todo::load() {
+            Ok(mut list) => {
+                let mut outcome = handle(&mut list, &line);
+                if outcome.changed {
+                    if let Err(e) = list.save() {
+                        let id = outcome.response.as_ref().map_or(Value::Null, |r| r["id"].clone());
+                        outcome.response = Some(error_response(id, RpcError::new(INTERNAL_ERROR, e.to_string())));
+                    }
+                    //Our own changes are not notified
+                    *last = modified(&path);
+                }
+                outcome
+            }
+            Err(e) => error_outcome(Value::Null, RpcError::new(INTERNAL_ERROR, e.to_string())),
+        };
+        if let Some(response) = outcome.response {
+            send(&response);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(list: &mut Todo, line: &str) -> Value {
+        handle(list, line).response.unwrap()
+    }
+
+    #[test]
+    fn methods_use_task_ids() {
+        let mut list = Todo::new();
+        let added = request(&mut list, r#"{"jsonrpc": "2.0", "id": 1, "method": "add", "params": {"name": "Task1", "priority": 3}}"#);
+        assert_eq!(added["id"], 1);
+        assert_eq!(added["result"]["name"], "Task1");
+        let task_id = added["result"]["id"].as_u64().unwrap();
+
+        let line = format!(r#"{{"jsonrpc": "2.0", "id": 2, "method": "rename", "params": {{"id": {}, "name": "Renamed"}}}}"#, task_id);
+        assert_eq!(request(&mut list, &line)["result"]["name"], "Renamed");
+        let line = format!(r#"{{"jsonrpc": "2.0", "id": 3, "method": "set_priority", "params": {{"id": {}, "priority": 8}}}}"#, task_id);
+        assert_eq!(request(&mut list, &line)["result"]["priority"], 8);
+        let line = format!(r#"{{"jsonrpc": "2.0", "id": 4, "method": "done", "params": {{"id": {}}}}}"#, task_id);
+        assert_eq!(request(&mut list, &line)["result"]["done"], true);
+
+        let items = request(&mut list, r#"{"jsonrpc": "2.0", "id": 5, "method": "items"}"#);
+        assert_eq!(items["result"].as_array().unwrap().len(), 1);
+        let line = format!(r#"{{"jsonrpc": "2.0", "id": 6, "method": "remove", "params": {{"ids": [{}]}}}}"#, task_id);
+        let outcome = handle(&mut list, &line);
+        assert!(outcome.changed);
+        assert!(list.items().is_empty());
+    }
+
+    #[test]
+    fn errors_and_notifications() {
+        let mut list = Todo::new();
+        assert_eq!(request(&mut list, "{oops")["error"]["code"], PARSE_ERROR);
+        assert_eq!(request(&mut list, r#"{"jsonrpc": "1.0", "id": 1, "method": "items"}"#)["error"]["code"], INVALID_REQUEST);
+        assert_eq!(request(&mut list, r#"{"jsonrpc": "2.0", "id": 1, "method": "nope"}"#)["error"]["code"], METHOD_NOT_FOUND);
+        let reply = request(&mut list, r#"{"jsonrpc": "2.0", "id": 1, "method": "done", "params": {"id": 9}}"#);
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+
+        let reply = request(&mut list, r#"{"jsonrpc": "2.0", "id": null, "method": "items"}"#);
+        assert_eq!(reply["id"], Value::Null);
+        assert!(reply["result"].is_array());
+
+        //A request without id still runs but gets no response
+        let outcome = handle(&mut list, r#"{"jsonrpc": "2.0", "method": "add", "params": {"name": "Quiet"}}"#);
+        assert_eq!(outcome.response, None);
+        assert!(outcome.changed);
+        assert_eq!(list.items().len(), 1);
+        let line = format!(r#"{{"jsonrpc": "2.0", "id": 2, "method": "rename", "params": {{"id": {}, "name": "  "}}}}"#, list.task(0).id);
+        assert_eq!(request(&mut list, &line)["error"]["code"], INVALID_PARAMS);
+        assert_eq!(list.task(0).name, "Quiet");
+    }
+}
@@ -0,0 +1,101 @@
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+///Version of the list file written by this program
+pub const VERSION: u64 = 1;
+
+///Upgrades a list from the version of its index to the next one
+type Migration = fn(&mut Map<String, Value>);
+
+///Migrations from each version to the next, files without version are version 0
+const MIGRATIONS: [Migration; VERSION as usize] = [give_ids];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SchemaError {
+    #[error("the list was written by a newer version of the application (version {0}, this one reads up to {VERSION}), please update it")]
+    Newer(u64),
+    #[error("the list is not a JSON object")]
+    NotAnObject,
+}
+
+///Version of a list file, 0 when it has none
+pub fn version(list: &Value) -> u64 {
+    list["version"].as_u64().unwrap_or(0)
+}
+
+///Fails when the list was written by a newer version, which this one could damage
+pub fn check(list: &Value) -> Result<(), SchemaError> {
+    match version(list) {
+        version if version > VERSION => Err(SchemaError::Newer(version)),
+        _ => Ok(()),
+    }
+}
+
+///Runs the migrations the list needs to reach the current version
+pub fn upgrade(list: &mut Value) -> Result<(), SchemaError> {
+    check(list)?;
+    let from = version(list) as usize;
+    let map = list.as_object_mut().ok_or(SchemaError::NotAnObject)?;
+    for migration in &MIGRATIONS[from..] {
+        migration(map);
+    }
+    map.insert("version".to_string(), json!(VERSION));
+    Ok(())
+}
+
+//==== Migrations
+
+//0 to 1: tasks get a stable id, and the list the next id to give
+fn give_ids(list: &mut Map<String, Value>) {
+    let in_list = list.get("list").and_then(Value::as_array).into_iter().flatten().map(|task| &task["id"]);
+    let in_trash = list.get("trash").and_then(Value::as_array).into_iter().flatten().map(|trashed| &trashed["task"]["id"]);
+    let max = in_list.chain(in_trash).filter_map(Value::as_u64).max().unwrap_or(0);
+    let mut next_id = list.get("next_id").and_then(Value::as_u64).unwrap_or(1).max(max + 1);
+    let mut give_id = |task: &mut Value| {
+        if task["id"].as_u64().unwrap_or(0) == 0 {
+            task["id"] = json!(next_id);
+            next_id += 1;
+        }
+    };
+    list.get_mut("list").and_then(Value::as_array_mut).into_iter().flatten().for_each(&mut give_id);
+    let in_trash = list.get_mut("trash").and_then(Value::as_array_mut).into_iter().flatten();
+    in_trash.map(|trashed| &mut trashed["task"]).for_each(&mut give_id);
+    list.insert("next_id".to_string(), json!(next_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_old_lists() {
+        let mut list = json!({ "list": [{ "name": "Task1", "priority": 1, "done": false }, { "id": 4, "name": "Task2", "priority": 1, "done": false }] });
+        upgrade(&mut list).unwrap();
+        assert_eq!(list["version"], VERSION);
+        assert_eq!(list["list"][0]["id"], 5);
+        assert_eq!(list["next_id"], 6);
+        //Upgrading again changes nothing
+        let upgraded = list.clone();
+        upgrade(&mut list).unwrap();
+        assert_eq!(list, upgraded);
+    }
+
+    #[test]
+    fn trashed_tasks_get_ids() {
+        let task = json!({ "name": "Task", "priority": 1, "done": false });
+        let trashed = json!({ "task": task, "deleted": "2024-01-01T00:00:00Z" });
+        let mut list = json!({ "list": [task], "trash": [trashed, trashed] });
+        upgrade(&mut list).unwrap();
+        assert_eq!(list["list"][0]["id"], 1);
+        assert_eq!(list["trash"][0]["task"]["id"], 2);
+        assert_eq!(list["trash"][1]["task"]["id"], 3);
+        assert_eq!(list["next_id"], 4);
+    }
+
+    #[test]
+    fn newer_lists_are_refused() {
+        let mut list = json!({ "version": VERSION + 1, "list": [] });
+        assert_eq!(upgrade(&mut list), Err(SchemaError::Newer(VERSION + 1)));
+        assert_eq!(upgrade(&mut json!([])), Err(SchemaError::NotAnObject));
+    }
+}
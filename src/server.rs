@@ -0,0 +1,274 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::error::Error;
+use tiny_http::{Header, Response, Server};
+
+use crate::todo::{DependencyError, Recurrence, Task, Todo};
+
+///Fields sent to create or update a task, missing fields are left unchanged.
+///An optional field set to null is removed
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TaskPatch {
+    pub name: Option<String>,
+    pub priority: Option<u8>,
+    pub done: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub depends_on: Option<Vec<u64>>,
+    #[serde(default, deserialize_with = "present")]
+    pub due: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "present")]
+    pub assignee: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub recurrence: Option<Option<Recurrence>>,
+}
+
+//Tells a null field from a missing one
+fn present<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl TaskPatch {
+    fn apply(&self, task: &mut Task) {
+        if let Some(name) = &self.name {
+            task.name = name.clone();
+        }
+        if let Some(priority) = self.priority {
+            task.priority = priority;
+        }
+        if let Some(tags) = &self.tags {
+            task.tags = tags.clone();
+        }
+        if let Some(due) = self.due {
+            task.due = due;
+        }
+        if let Some(assignee) = &self.assignee {
+            task.assignee = assignee.clone();
+        }
+        if let Some(recurrence) = self.recurrence {
+            task.recurrence = recurrence;
+        }
+    }
+}
+
+///Answer to a request: a status code and an optional JSON body
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub body: Option<Value>,
+    ///The list was modified and must be saved
+    pub changed: bool,
+}
+
+impl Reply {
+    fn ok(body: Value) -> Self {
+        Reply { status: 200, body: Some(body), changed: false }
+    }
+
+    fn changed(status: u16, body: Option<Value>) -> Self {
+        Reply { status, body, changed: true }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Reply { status, body: Some(json!({ "error": message })), changed: false }
+    }
+
+    fn with_status(mut self, status: u16) -> Self {
+        if self.status == 200 {
+            self.status = status;
+        }
+        self
+    }
+}
+
+///Runs a request on the list:
+///- GET /tasks, GET /tasks/{id}
+///- POST /tasks, PATCH /tasks/{id}, POST /tasks/{id}/complete, DELETE /tasks/{id}
+pub fn handle(list: &mut Todo, method: &str, url: &str, body: &str) -> Reply {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let (id, action) = match segments.as_slice() {
+        ["tasks"] => (None, None),
+        ["tasks", id] => (Some(*id), None),
+        ["tasks", id, action] => (Some(*id), Some(*action)),
+        _ => return Reply::error(404, "Not found"),
+    };
+    let index = match id.map(|id| id.parse::<u64>().ok().and_then(|id| list.position(id))) {
+        Some(None) => return Reply::error(404, "No such task"),
+        Some(Some(index)) => Some(index),
+        None => None,
+    };
+
+    match (method, index, action) {
+        ("GET", None, None) => Reply::ok(json!(list.items())),
+        ("GET", Some(index), None) => Reply::ok(json!(list.task(index))),
+        ("POST", None, None) => {
+            let patch: TaskPatch = match serde_json::from_str(body) {
+                Ok(patch) => patch,
+                Err(e) => return Reply::error(400, &e.to_string()),
+            };
+            let Some(name) = patch.name.as_deref().filter(|name| !name.trim().is_empty()) else {
+                return Reply::error(400, "The task needs a name");
+            };
+            if let Err(e) = validate(list, &patch) {
+                return e;
+            }
+            let id = list.insert(Task::new(name, 0));
+            update(list, id, &patch).unwrap_or_else(|e| e).with_status(201)
+        }
+        ("PATCH" | "PUT", Some(index), None) => {
+            let patch: TaskPatch = match serde_json::from_str(body) {
+                Ok(patch) => patch,
+                Err(e) => return Reply::error(400, &e.to_string()),
+            };
+            let id = list.task(index).id;
+            update(list, id, &patch).unwrap_or_else(|e| e)
+        }
+        ("POST", Some(index), Some("complete")) => {
+            let id = list.task(index).id;
+            list.set_done(index, true);
+            task_reply(list, id)
+        }
+        ("DELETE", Some(index), None) => match list.remove(&[index]) {
+            Ok(()) => Reply::changed(204, None),
+            Err(()) => Reply::error(404, "No such task"),
+        },
+        _ => Reply::error(405, "Method not allowed"),
+    }
+}
+
+//Applies a patch to a task, nothing is changed if a field is invalid
+fn update(list: &mut Todo, id: u64, patch: &TaskPatch) -> Result<Reply, Reply> {
+    let index = list.position(id).ok_or(Reply::error(404, "No such task"))?;
+    validate(list, patch)?;
+    if let Some(depends_on) = &patch.depends_on {
+        list.set_dependencies(index, depends_on).map_err(|e| Reply::error(400, &e.to_string()))?;
+    }
+    let index = list.position(id).ok_or(Reply::error(404, "No such task"))?;
+    list.update(index, |task| patch.apply(task));
+    if let Some(done) = patch.done {
+        if let Some(index) = list.position(id) {
+            list.set_done(index, done);
+        }
+    }
+    Ok(task_reply(list, id))
+}
+
+//Checks the fields that do not depend on the task itself
+fn validate(list: &Todo, patch: &TaskPatch) -> Result<(), Reply> {
+    if patch.name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+        return Err(Reply::error(400, "The task needs a name"));
+    }
+    if patch.priority.is_some_and(|priority| priority > 10) {
+        return Err(Reply::error(400, "The priority must be between 0 and 10"));
+    }
+    let unknown = patch.depends_on.iter().flatten().find(|id| list.position(**id).is_none());
+    match unknown {
+        Some(id) => Err(Reply::error(400, &DependencyError::UnknownTask(*id).to_string())),
+        None => Ok(()),
+    }
+}
+
+fn task_reply(list: &Todo, id: u64) -> Reply {
+    match list.position(id) {
+        Some(index) => Reply::changed(200, Some(json!(list.task(index)))),
+        None => Reply::error(404, "No such task"),
+    }
+}
+
+///Serves the API on localhost, the list is read again for each request
+///so changes made by the interface are seen
+pub fn serve(port: u16) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(("127.0.0.1", port))?;
+    println!("Listening on http://127.0.0.1:{}", port);
+    let json_header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let reply = match request.as_reader().read_to_string(&mut body) {
+            Err(e) => Reply::error(400, &e.to_string()),
+            Ok(_) => match Todo::load() {
+                Err(e) => Reply::error(500, &e.to_string()),
+                Ok(mut list) => {
+                    let reply = handle(&mut list, request.method().as_str(), request.url(), &body);
+                    match reply.changed.then(|| list.save()) {
+                        Some(Err(e)) => Reply::error(500, &e.to_string()),
+                        _ => reply,
+                    }
+                }
+            },
+        };
+        let response = match reply.body {
+            Some(body) => Response::from_string(body.to_string()).with_header(json_header.clone()),
+            None => Response::from_string(""),
+        };
+        let _ = request.respond(response.with_status_code(reply.status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_get_and_list() {
+        let mut list = Todo::new();
+        let reply = handle(&mut list, "POST", "/tasks", r#"{"name": "Write docs", "priority": 4, "tags": ["doc"]}"#);
+        assert_eq!(reply.status, 201);
+        assert!(reply.changed);
+        let body = reply.body.unwrap();
+        assert_eq!(body["name"], "Write docs");
+        assert_eq!(body["priority"], 4);
+
+        let id = body["id"].as_u64().unwrap();
+        let reply = handle(&mut list, "GET", &format!("/tasks/{}", id), "");
+        assert_eq!(reply.body.unwrap()["tags"], json!(["doc"]));
+        let reply = handle(&mut list, "GET", "/tasks", "");
+        assert_eq!(reply.body.unwrap().as_array().unwrap().len(), 1);
+        assert!(!reply.changed);
+    }
+
+    #[test]
+    fn update_complete_and_delete() {
+        let mut list = Todo::new();
+        list.add("Task1", 1);
+        let reply = handle(&mut list, "PATCH", "/tasks/1", r#"{"priority": 9, "due": "2024-03-01", "assignee": "bob"}"#);
+        assert_eq!(reply.status, 200);
+        assert_eq!(list.task(0).priority, 9);
+        assert_eq!(list.task(0).due, NaiveDate::from_ymd_opt(2024, 3, 1));
+        handle(&mut list, "PATCH", "/tasks/1", r#"{"due": null}"#);
+        assert_eq!(list.task(0).due, None);
+        assert_eq!(list.task(0).assignee.as_deref(), Some("bob"));
+
+        let reply = handle(&mut list, "POST", "/tasks/1/complete", "");
+        assert_eq!(reply.body.unwrap()["done"], true);
+        let reply = handle(&mut list, "DELETE", "/tasks/1", "");
+        assert_eq!(reply.status, 204);
+        assert!(list.items().is_empty());
+        assert_eq!(list.trash().len(), 1);
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let mut list = Todo::new();
+        list.add("Task1", 1);
+        assert_eq!(handle(&mut list, "GET", "/tasks/42", "").status, 404);
+        assert_eq!(handle(&mut list, "GET", "/other", "").status, 404);
+        assert_eq!(handle(&mut list, "PUT", "/tasks", "").status, 405);
+        assert_eq!(handle(&mut list, "POST", "/tasks", "{}").status, 400);
+        assert_eq!(handle(&mut list, "POST", "/tasks", r#"{"name": "A", "colour": 1}"#).status, 400);
+        let reply = handle(&mut list, "PATCH", "/tasks/1", r#"{"depends_on": [1]}"#);
+        assert_eq!(reply.status, 400);
+        assert!(!reply.changed);
+        let reply = handle(&mut list, "PATCH", "/tasks/1", r#"{"name": "  "}"#);
+        assert_eq!(reply.status, 400);
+        assert_eq!(list.task(0).name, "Task1");
+        let reply = handle(&mut list, "POST", "/tasks", r#"{"name": "B", "priority": 11}"#);
+        assert_eq!(reply.status, 400);
+        let reply = handle(&mut list, "POST", "/tasks", r#"{"name": "B", "depends_on": [7]}"#);
+        assert_eq!(reply.status, 400);
+        assert_eq!(list.items().len(), 1);
+        assert!(list.trash().is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeDelta};
+
+use crate::todo::{Task, Tier, Todo};
+
+///Figures displayed in the statistics screen
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub open: usize,
+    pub done: usize,
+    ///Open tasks by priority tier, in the order of `Tier::ALL`
+    pub by_priority: [usize; 3],
+    ///Tasks completed on each of the last days, oldest first
+    pub done_per_day: Vec<u64>,
+    ///Tasks completed on each of the last weeks, oldest first
+    pub done_per_week: Vec<(NaiveDate, u64)>,
+    ///Percentage of the existing tasks that were done at the end of each week, oldest first
+    pub completion_rate: Vec<(NaiveDate, f64)>,
+    ///Mean time between creation and completion
+    pub average_time_to_done: Option<TimeDelta>,
+}
+
+impl Stats {
+    pub const DAYS: usize = 30;
+    pub const WEEKS: usize = 8;
+
+    pub fn compute(todo: &Todo, today: NaiveDate) -> Self {
+        let tasks = todo.items();
+        let done = tasks.iter().filter(|task| task.done).count();
+        let mut by_priority = [0; 3];
+        for task in tasks.iter().filter(|task| !task.done) {
+            by_priority[Tier::of(task.priority) as usize] += 1;
+        }
+
+        let done_per_day = (0..Self::DAYS)
+            .rev()
+            .map(|days_ago| {
+                let day = today - Duration::days(days_ago as i64);
+                tasks.iter().filter(|task| completed_on(task) == Some(day)).count() as u64
+            })
+            .collect();
+
+        let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let weeks: Vec<NaiveDate> = (0..Self::WEEKS)
+            .rev()
+            .map(|weeks_ago| week_start - Duration::weeks(weeks_ago as i64))
+            .collect();
+        let done_per_week = weeks
+            .iter()
+            .map(|start| {
+                let end = *start + Duration::days(7);
+                let count = tasks
+                    .iter()
+                    .filter(|task| completed_on(task).is_some_and(|day| day >= *start && day < end))
+                    .count();
+                (*start, count as u64)
+            })
+            .collect();
+        let completion_rate = weeks
+            .iter()
+            .map(|start| {
+                let end = *start + Duration::days(7);
+                let existing: Vec<&Task> = tasks
+                    .iter()
+                    .filter(|task| created_on(task).is_none_or(|day| day < end))
+                    .collect();
+                let done = existing
+                    .iter()
+                    .filter(|task| completed_on(task).is_some_and(|day| day < end))
+                    .count();
+                let rate = if existing.is_empty() { 0.0 } else { done as f64 * 100.0 / existing.len() as f64 };
+                (*start, rate)
+            })
+            .collect();
+
+        let durations: Vec<TimeDelta> = tasks
+            .iter()
+            .filter_map(|task| Some(task.completed? - task.created?))
+            .collect();
+        let average_time_to_done = if durations.is_empty() {
+            None
+        } else {
+            Some(durations.iter().sum::<TimeDelta>() / durations.len() as i32)
+        };
+
+        Stats {
+            open: tasks.len() - done,
+            done,
+            by_priority,
+            done_per_day,
+            done_per_week,
+            completion_rate,
+            average_time_to_done,
+        }
+    }
+}
+
+fn completed_on(task: &Task) -> Option<NaiveDate> {
+    Some(task.completed?.with_timezone(&Local).date_naive())
+}
+
+fn created_on(task: &Task) -> Option<NaiveDate> {
+    Some(task.created?.with_timezone(&Local).date_naive())
+}
+
+///Writes a duration like "3d 4h" or "25m"
+pub fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes().max(0);
+    match (minutes / (24 * 60), minutes / 60 % 24, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_counts_and_history() {
+        let mut todo = Todo::new();
+        todo.add("Open", 7);
+        todo.add("Done", 1);
+        todo.add("Low", 1);
+        todo.done(1);
+        let today = Local::now().date_naive();
+        let stats = Stats::compute(&todo, today);
+        assert_eq!(stats.open, 2);
+        assert_eq!(stats.done, 1);
+        assert_eq!(stats.by_priority, [1, 0, 1]);
+        assert_eq!(stats.done_per_day.len(), Stats::DAYS);
+        assert_eq!(stats.done_per_day.last(), Some(&1));
+        assert_eq!(stats.done_per_week.last().map(|w| w.1), Some(1));
+        let rate = stats.completion_rate.last().unwrap().1;
+        assert!((rate - 100.0 / 3.0).abs() < 0.01);
+        assert!(stats.average_time_to_done.is_some());
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(TimeDelta::minutes(5)), "5m");
+        assert_eq!(format_duration(TimeDelta::minutes(65)), "1h 5m");
+        assert_eq!(format_duration(TimeDelta::hours(49)), "2d 1h");
+    }
+}
@@ -0,0 +1,522 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+use crate::crypto::{self, CryptoError};
+use crate::history;
+use crate::schema::{self, SchemaError};
+use crate::todo::Todo;
+
+///Set at startup when every save must be committed
+static AUTO_COMMIT: AtomicBool = AtomicBool::new(false);
+
+///Longest commit subject, longer descriptions go in the body
+const SUBJECT_LENGTH: usize = 72;
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("{0}")]
+    Git(String),
+    #[error("{0} is not in a git repository, run `git init` there first")]
+    NotARepository(String),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Unsupported file: {0}")]
+    Schema(#[from] SchemaError),
+    #[error("Encryption error: {0}")]
+    Crypto(#[from] CryptoError),
+}
+
+///Git settings of the directory holding the list
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SyncSettings {
+    ///Commits the list each time it is saved
+    pub enabled: bool,
+    ///Remote used by `todo sync`
+    pub remote: String,
+    ///Branch pulled and pushed by `todo sync`
+    pub branch: String,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        SyncSettings { enabled: false, remote: "origin".to_string(), branch: "main".to_string() }
+    }
+}
+
+impl SyncSettings {
+    pub fn is_valid(&self) -> bool {
+        !self.remote.trim().is_empty() && !self.branch.trim().is_empty()
+    }
+}
+
+///Result of `todo sync`
+#[derive(Debug, PartialEq)]
+pub enum Synced {
+    UpToDate,
+    Pushed,
+    Pulled,
+    Merged,
+}
+
+///Makes `Todo::save` commit the list when sync is enabled
+pub fn enable(settings: &SyncSettings) {
+    AUTO_COMMIT.store(settings.enabled, Ordering::Relaxed);
+}
+
+///Commits the list file if sync is enabled, called after each save
+pub fn commit(path: &str) -> Result<(), SyncError> {
+    if AUTO_COMMIT.load(Ordering::Relaxed) {
+        commit_file(path)?;
+    }
+    Ok(())
+}
+
+///Commits the list file with a message describing the changes, returns false if nothing changed
+pub fn commit_file(path: &str) -> Result<bool, SyncError> {
+    let (dir, file) = locate(path)?;
+    git(&dir, &["add", "--", &file])?;
+    if succeeds(&dir, &["diff", "--cached", "--quiet", "--", &file]) {
+        return Ok(false);
+    }
+    let text = fs::read_to_string(path)?;
+    //The names of the tasks of an encrypted list are kept out of the messages
+    let message = if crypto::is_encrypted(&text) {
+        "update encrypted task list".to_string()
+    } else {
+        //An unreadable earlier version only makes the message less precise
+        let old = show(&dir, "HEAD", &file).unwrap_or(None);
+        describe(old.as_ref(), &serde_json::from_str(&text)?)
+    };
+    git(&dir, &["commit", "--quiet", "-m", &message, "--", &file])?;
+    Ok(true)
+}
+
+///Commits local changes, then pulls and pushes the branch of the remote.
+///Diverged lists are merged task by task instead of line by line
+pub fn sync(settings: &SyncSettings, path: &str) -> Result<Synced, SyncError> {
+    let (dir, file) = locate(path)?;
+    commit_file(path)?;
+    let remote = settings.remote.as_str();
+    let branch = settings.branch.as_str();
+    let push = format!("HEAD:refs/heads/{}", branch);
+
+    if git(&dir, &["ls-remote", "--heads", remote, branch])?.trim().is_empty() {
+        git(&dir, &["push", "--quiet", remote, &push])?;
+        return Ok(Synced::Pushed);
+    }
+    git(&dir, &["fetch", "--quiet", remote, branch])?;
+    let has_head = succeeds(&dir, &["rev-parse", "--verify", "--quiet", "HEAD"]);
+    if has_head && succeeds(&dir, &["merge-base", "--is-ancestor", "FETCH_HEAD", "HEAD"]) {
+        if git(&dir, &["rev-parse", "HEAD"])? == git(&dir, &["rev-parse", "FETCH_HEAD"])? {
+            return Ok(Synced::UpToDate);
+        }
+        git(&dir, &["push", "--quiet", remote, &push])?;
+        return Ok(Synced::Pushed);
+    }
+    if !has_head || succeeds(&dir, &["merge-base", "--is-ancestor", "HEAD", "FETCH_HEAD"]) {
+        git(&dir, &["merge", "--quiet", "--ff-only", "FETCH_HEAD"])?;
+        return Ok(Synced::Pulled);
+    }
+
+    //A version that can't be read stops the merge, it would be taken for an empty list
+    let base = match git(&dir, &["merge-base", "HEAD", "FETCH_HEAD"]) {
+        Ok(base) => show(&dir, base.trim(), &file)?,
+        Err(_) => None,
+    };
+    let empty = serde_json::to_value(Todo::new())?;
+    let mut versions = [
+        base.unwrap_or(empty.clone()),
+        show(&dir, "HEAD", &file)?.unwrap_or(empty.clone()),
+        show(&dir, "FETCH_HEAD", &file)?.unwrap_or(empty),
+    ];
+    //Lists written by a newer version are not merged, they could lose data
+    for version in versions.iter_mut() {
+        schema::upgrade(version)?;
+    }
+    let [base, ours, theirs] = versions;
+    let mut list: Todo = serde_json::from_value(merge_lists(&base, &ours, &theirs))?;
+    list.set_sort_mode(list.sort_mode());
+
+    //The list usually conflicts as text, it is replaced by the merged one before committing
+    let merge = git(&dir, &["merge", "--quiet", "--no-commit", "--no-ff", "--allow-unrelated-histories", "FETCH_HEAD"]);
+    if !succeeds(&dir, &["rev-parse", "--verify", "--quiet", "MERGE_HEAD"]) {
+        merge?;
+    }
+    fs::write(path, crypto::seal(&serde_json::to_string(&list)?)?)?;
+    git(&dir, &["add", "--", &file])?;
+    let message = format!("merge: {}/{}", remote, branch);
+    git(&dir, &["commit", "--quiet", "-m", &message])?;
+    git(&dir, &["push", "--quiet", remote, &push])?;
+    Ok(Synced::Merged)
+}
+
+//==== Git
+
+//Directory and name of the list file
+fn locate(path: &str) -> Result<(PathBuf, String), SyncError> {
+    let path = Path::new(path);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if !succeeds(&dir, &["rev-parse", "--is-inside-work-tree"]) {
+        return Err(SyncError::NotARepository(dir.display().to_string()));
+    }
+    Ok((dir, file))
+}
+
+//Runs git in a directory, returns its output or its error message
+fn git(dir: &Path, args: &[&str]) -> Result<String, SyncError> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let error = String::from_utf8_lossy(&output.stderr);
+        Err(SyncError::Git(format!("git {} failed: {}", args[0], error.trim())))
+    }
+}
+
+fn succeeds(dir: &Path, args: &[&str]) -> bool {
+    git(dir, args).is_ok()
+}
+
+//The list file at a revision, None if it did not exist
+fn show(dir: &Path, revision: &str, file: &str) -> Result<Option<Value>, SyncError> {
+    let object = format!("{}:./{}", revision, file);
+    if !succeeds(dir, &["cat-file", "-e", &object]) {
+        return Ok(None);
+    }
+    let text = crypto::open(git(dir, &["show", &object])?)?;
+    Ok(Some(serde_json::from_str(&text)?))
+}
+
+//==== Commit messages
+
+//Kinds of change, in the order they are written
+const KINDS: [&str; 6] = ["add", "restore", "done", "reopen", "rename", "update"];
+
+fn tasks(list: &Value, key: &str) -> Vec<Value> {
+    let tasks = list[key].as_array().cloned().unwrap_or_default();
+    match key {
+        "trash" => tasks.into_iter().map(|trashed| trashed["task"].clone()).collect(),
+        _ => tasks,
+    }
+}
+
+fn by_id(tasks: Vec<Value>) -> BTreeMap<u64, Value> {
+    tasks.into_iter().filter_map(|task| Some((task["id"].as_u64()?, task))).collect()
+}
+
+fn name(task: &Value) -> String {
+    task["name"].as_str().unwrap_or_default().to_string()
+}
+
+///Describes the changes between two versions of the list, like "done: Fix login bug"
+fn describe(old: Option<&Value>, new: &Value) -> String {
+    let Some(old) = old else {
+        return "create task list".to_string();
+    };
+    let old_list = by_id(tasks(old, "list"));
+    let old_trash = by_id(tasks(old, "trash"));
+    let new_list = tasks(new, "list");
+    let new_ids: BTreeSet<u64> = new_list.iter().filter_map(|task| task["id"].as_u64()).collect();
+
+    let mut changes: Vec<(&str, String)> = vec![];
+    for task in &new_list {
+        let id = task["id"].as_u64().unwrap_or_default();
+        let kind = match old_list.get(&id) {
+            None if old_trash.contains_key(&id) => "restore",
+            None => "add",
+            Some(previous) if previous == task => continue,
+            Some(previous) if previous["done"] != task["done"] => match task["done"].as_bool() {
+                Some(true) => "done",
+                _ => "reopen",
+            },
+            Some(previous) if previous["name"] != task["name"] => "rename",
+            Some(_) => "update",
+        };
+        changes.push((kind, name(task)));
+    }
+    let mut parts: Vec<String> = KINDS
+        .iter()
+        .filter_map(|kind| {
+            let names: Vec<&str> = changes.iter().filter(|c| c.0 == *kind).map(|c| c.1.as_str()).collect();
+            (!names.is_empty()).then(|| format!("{}: {}", kind, names.join(", ")))
+        })
+        .collect();
+    let removed: Vec<String> = old_list.iter().filter(|(id, _)| !new_ids.contains(id)).map(|(_, task)| name(task)).collect();
+    if !removed.is_empty() {
+        parts.push(format!("remove: {}", removed.join(", ")));
+    }
+
+    let count = changes.len() + removed.len();
+    match parts.join("; ") {
+        subject if subject.is_empty() => "update list settings".to_string(),
+        subject if subject.len() <= SUBJECT_LENGTH => subject,
+        _ => format!("update {} tasks\n\n{}", count, parts.join("\n")),
+    }
+}
+
+//==== Merge
+
+///Three-way merge of two versions of the list. Tasks are matched by id and merged field by field,
+///our side wins when both changed the same field. A task removed on one side and modified
+///on the other is kept. Tasks added on both sides with the same id are renumbered on our side,
+///as their ids were already pushed
+fn merge_lists(base: &Value, ours: &Value, theirs: &Value) -> Value {
+    let ours = &renumber(ours, &ids(base), &ids(theirs));
+
+    let base_list = by_id(tasks(base, "list"));
+    let our_list = by_id(tasks(ours, "list"));
+    let their_list = by_id(tasks(theirs, "list"));
+    let order = tasks(ours, "list").into_iter().chain(tasks(theirs, "list")).filter_map(|task| task["id"].as_u64());
+
+    let mut list: Vec<Value> = vec![];
+    let mut seen = BTreeSet::new();
+    for id in order {
+        if !seen.insert(id) {
+            continue;
+        }
+        let base = base_list.get(&id);
+        let task = match (our_list.get(&id), their_list.get(&id)) {
+            (Some(ours), Some(theirs)) => merge_task(base, ours, theirs),
+            //Removed on the other side, kept only if modified here
+            (Some(task), None) | (None, Some(task)) => (base != Some(task)).then(|| task.clone()),
+            (None, None) => None,
+        };
+        list.extend(task);
+    }
+
+    //Trashed tasks still in the list were restored
+    let mut trashed_ids: BTreeSet<u64> = list.iter().filter_map(|task| task["id"].as_u64()).collect();
+    let mut trash: Vec<Value> = vec![];
+    for trashed in ours["trash"].as_array().into_iter().chain(theirs["trash"].as_array()).flatten() {
+        let id = trashed["task"]["id"].as_u64().unwrap_or_default();
+        seen.insert(id);
+        if trashed_ids.insert(id) {
+            trash.push(trashed.clone());
+        }
+    }
+
+    let mut merged = Map::new();
+    let keys: BTreeSet<&String> = [base, ours, theirs].iter().filter_map(|list| list.as_object()).flat_map(|list| list.keys()).collect();
+    for key in keys {
+        if let Some(value) = merge_value(base.get(key), ours.get(key), theirs.get(key)) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    let next_id = [ours, theirs].iter().filter_map(|list| list["next_id"].as_u64()).chain(seen.iter().map(|id| id + 1)).max();
+    merged.insert("next_id".to_string(), Value::from(next_id.unwrap_or(1)));
+    merged.insert("list".to_string(), Value::Array(list));
+    merged.insert("trash".to_string(), Value::Array(trash));
+    Value::Object(merged)
+}
+
+//A task changed on both sides, field by field, except its history which is the union of both
+fn merge_task(base: Option<&Value>, ours: &Value, theirs: &Value) -> Option<Value> {
+    let mut task = merge_value(Some(base.unwrap_or(&Value::Object(Map::new()))), Some(ours), Some(theirs))?;
+    if ours.get("history").is_some() || theirs.get("history").is_some() {
+        task["history"] = history::merge(&ours["history"], &theirs["history"]);
+    }
+    Some(task)
+}
+
+//Ids of the tasks in the list and the trash
+fn ids(list: &Value) -> BTreeSet<u64> {
+    by_id(tasks(list, "list")).into_keys().chain(by_id(tasks(list, "trash")).into_keys()).collect()
+}
+
+//Gives new ids to the tasks we added with an id they also added
+fn renumber(ours: &Value, base_ids: &BTreeSet<u64>, their_ids: &BTreeSet<u64>) -> Value {
+    let our_ids = ids(ours);
+    let next = base_ids.iter().chain(their_ids).chain(&our_ids).max().map_or(1, |max| max + 1);
+    let added = by_id(tasks(ours, "list")).into_keys().filter(|id| !base_ids.contains(id) && their_ids.contains(id));
+    let new_ids: BTreeMap<u64, u64> = added.zip(next..).collect();
+
+    let mut ours = ours.clone();
+    if let Some(list) = ours["list"].as_array_mut() {
+        for task in list {
+            if let Some(id) = task["id"].as_u64().and_then(|id| new_ids.get(&id)) {
+                task["id"] = Value::from(*id);
+            }
+            for dependency in task.get_mut("depends_on").and_then(Value::as_array_mut).into_iter().flatten() {
+                if let Some(id) = dependency.as_u64().and_then(|id| new_ids.get(&id)) {
+                    *dependency = Value::from(*id);
+                }
+            }
+        }
+    }
+    ours
+}
+
+//Three-way merge of a value, objects are merged key by key
+fn merge_value(base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    match (ours, theirs) {
+        (Some(Value::Object(our_map)), Some(Value::Object(their_map))) => {
+            let empty = Map::new();
+            let base_map = base.and_then(Value::as_object).unwrap_or(&empty);
+            let keys: BTreeSet<&String> = base_map.keys().chain(our_map.keys()).chain(their_map.keys()).collect();
+            let merged = keys
+                .into_iter()
+                .filter_map(|key| Some((key.clone(), merge_value(base_map.get(key), our_map.get(key), their_map.get(key))?)))
+                .collect();
+            Some(Value::Object(merged))
+        }
+        _ => ours.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task(id: u64, name: &str, priority: u8, done: bool) -> Value {
+        json!({ "id": id, "name": name, "priority": priority, "done": done })
+    }
+
+    #[test]
+    fn merge_tasks_field_by_field() {
+        let base = json!({ "sort": "priority", "next_id": 3, "list": [task(1, "Fix bug", 1, false), task(2, "Docs", 1, false)], "trash": [] });
+        let ours = json!({ "sort": "priority", "next_id": 4,
+            "list": [task(1, "Fix login bug", 1, false), task(2, "Docs", 1, false), task(3, "Ours", 1, false)], "trash": [] });
+        let theirs = json!({ "sort": "due", "next_id": 4,
+            "list": [task(1, "Fix bug", 1, true), task(3, "Theirs", 2, false)],
+            "trash": [{ "task": task(2, "Docs", 1, false), "deleted": "2024-03-01T10:00:00Z" }] });
+
+        let merged = merge_lists(&base, &ours, &theirs);
+        let list = by_id(tasks(&merged, "list"));
+        assert_eq!(list[&1], task(1, "Fix login bug", 1, true));
+        assert_eq!(list[&3], task(3, "Theirs", 2, false));
+        assert_eq!(list[&4], task(4, "Ours", 1, false));
+        assert!(!list.contains_key(&2));
+        assert_eq!(tasks(&merged, "trash"), vec![task(2, "Docs", 1, false)]);
+        assert_eq!(merged["sort"], "due");
+        assert_eq!(merged["next_id"], 5);
+        serde_json::from_value::<Todo>(merged).unwrap();
+    }
+
+    #[test]
+    fn merge_histories() {
+        let change = |at: &str, author: &str| json!({ "at": at, "author": author, "change": "done" });
+        let mut base = task(1, "Fix bug", 1, false);
+        base["history"] = json!([change("2024-03-01T10:00:00Z", "ann")]);
+        let mut ours = task(1, "Fix bug", 1, true);
+        ours["history"] = json!([change("2024-03-01T10:00:00Z", "ann"), change("2024-03-03T10:00:00Z", "ann")]);
+        let mut theirs = task(1, "Fix bug", 1, true);
+        theirs["history"] = json!([change("2024-03-01T10:00:00Z", "ann"), change("2024-03-02T10:00:00Z", "bob")]);
+        let list = |task: &Value| json!({ "list": [task], "trash": [] });
+
+        let merged = merge_lists(&list(&base), &list(&ours), &list(&theirs));
+        let history = &tasks(&merged, "list")[0]["history"];
+        let authors: Vec<&str> = history.as_array().unwrap().iter().map(|change| change["author"].as_str().unwrap()).collect();
+        assert_eq!(authors, vec!["ann", "bob", "ann"]);
+    }
+
+    #[test]
+    fn describe_changes() {
+        let old = json!({ "list": [task(1, "Fix login bug", 1, false), task(2, "Docs", 1, false)], "trash": [] });
+        let new = json!({ "list": [task(1, "Fix login bug", 1, true), task(2, "Docs", 5, false)], "trash": [] });
+        assert_eq!(describe(Some(&old), &new), "done: Fix login bug; update: Docs");
+        let new = json!({ "list": [task(2, "Docs", 1, false), task(3, "Release", 1, false)], "trash": [] });
+        assert_eq!(describe(Some(&old), &new), "add: Release; remove: Fix login bug");
+        assert_eq!(describe(Some(&old), &json!({ "views": {}, "list": old["list"] })), "update list settings");
+        assert_eq!(describe(None, &new), "create task list");
+    }
+
+    //Runs git and panics on failure
+    fn run(dir: &Path, args: &[&str]) -> String {
+        git(dir, args).unwrap()
+    }
+
+    fn replica(root: &Path, name: &str, remote: &Path) -> PathBuf {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        run(&dir, &["init", "--quiet", "--initial-branch", "main"]);
+        run(&dir, &["config", "user.name", name]);
+        run(&dir, &["config", "user.email", "todo@example.com"]);
+        run(&dir, &["remote", "add", "origin", &remote.display().to_string()]);
+        dir
+    }
+
+    #[test]
+    fn encrypted_versions_need_the_secret() {
+        let root = std::env::temp_dir().join(format!("todo-sync-encrypted-{}", std::process::id()));
+        let dir = replica(&root, "a", &root.join("remote.git"));
+        let list = serde_json::to_string(&Todo::new()).unwrap();
+        fs::write(dir.join("tasks.json"), crypto::encrypt(b"correct horse", &list).unwrap()).unwrap();
+        run(&dir, &["add", "tasks.json"]);
+        run(&dir, &["commit", "--quiet", "-m", "encrypted"]);
+        //The merge stops instead of taking the list for an empty one
+        assert!(matches!(show(&dir, "HEAD", "tasks.json"), Err(SyncError::Crypto(CryptoError::Locked))));
+        assert!(matches!(show(&dir, "HEAD", "other.json"), Ok(None)));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sync_through_a_bare_remote() {
+        let root = std::env::temp_dir().join(format!("todo-sync-{}", std::process::id()));
+        let remote = root.join("remote.git");
+        fs::create_dir_all(&remote).unwrap();
+        run(&remote, &["init", "--quiet", "--bare"]);
+        let a = replica(&root, "a", &remote);
+        let b = replica(&root, "b", &remote);
+        let path_a = a.join("tasks.json").display().to_string();
+        let path_b = b.join("tasks.json").display().to_string();
+        let settings = SyncSettings::default();
+
+        let mut list = Todo::new();
+        list.add("Task A", 1);
+        list.save_to(path_a.clone()).unwrap();
+        assert_eq!(sync(&settings, &path_a).unwrap(), Synced::Pushed);
+
+        //Both replicas created a task with id 1
+        let mut list = Todo::new();
+        list.add("Task B", 2);
+        list.save_to(path_b.clone()).unwrap();
+        assert_eq!(sync(&settings, &path_b).unwrap(), Synced::Merged);
+
+        let mut list: Todo = serde_json::from_str(&fs::read_to_string(&path_a).unwrap()).unwrap();
+        list.set_done(0, true);
+        list.save_to(path_a.clone()).unwrap();
+        assert!(commit_file(&path_a).unwrap());
+        assert_eq!(run(&a, &["log", "-1", "--format=%s"]).trim(), "done: Task A");
+        assert_eq!(sync(&settings, &path_a).unwrap(), Synced::Merged);
+        assert_eq!(sync(&settings, &path_b).unwrap(), Synced::Pulled);
+        assert_eq!(sync(&settings, &path_b).unwrap(), Synced::UpToDate);
+
+        let list: Todo = serde_json::from_str(&fs::read_to_string(&path_b).unwrap()).unwrap();
+        let mut names: Vec<(&str, bool)> = list.items().iter().map(|task| (task.name.as_str(), task.done)).collect();
+        names.sort();
+        assert_eq!(names, vec![("Task A", true), ("Task B", false)]);
+        let ids: BTreeSet<u64> = list.items().iter().map(|task| task.id).collect();
+        assert_eq!(ids.len(), 2);
+
+        //A damaged list on the remote stops the merge instead of being read as an empty list
+        fs::write(&path_a, "{oops").unwrap();
+        run(&a, &["commit", "--quiet", "-am", "damage"]);
+        run(&a, &["push", "--quiet", "origin", "HEAD:refs/heads/main"]);
+        let mut list: Todo = serde_json::from_str(&fs::read_to_string(&path_b).unwrap()).unwrap();
+        list.add("Task C", 1);
+        list.save_to(path_b.clone()).unwrap();
+        assert!(matches!(sync(&settings, &path_b), Err(SyncError::SerializationError(_))));
+        assert!(fs::read_to_string(&path_b).unwrap().contains("Task C"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
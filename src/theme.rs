@@ -0,0 +1,267 @@
+use ratatui::style::{
+    palette::tailwind::{AMBER, BLUE, SLATE, TEAL},
+    Color, Modifier, Style,
+};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+
+use crate::config::ConfigError;
+use crate::todo::Tier;
+
+///Colors used by the interface, a theme file only needs the slots it changes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    pub title_fg: Color,
+    pub title_bg: Color,
+    pub header_fg: Color,
+    pub header_bg: Color,
+    pub row_bg: Color,
+    pub alt_row_bg: Color,
+    pub text_fg: Color,
+    pub label_fg: Color,
+    pub selected_bg: Color,
+    pub marked_bg: Color,
+    pub edit_fg: Color,
+    pub edit_bg: Color,
+    pub edit_value_bg: Color,
+    pub footer_fg: Color,
+    pub footer_bg: Color,
+    pub priority_low_fg: Color,
+    pub priority_medium_fg: Color,
+    pub priority_high_fg: Color,
+}
+
+impl Default for Theme {
+    ///The teal theme, or the no-color one when NO_COLOR is set, also used when the config can't be read
+    fn default() -> Self {
+        if env::var(Self::NO_COLOR_VAR).is_ok_and(|v| !v.is_empty()) {
+            return Self::no_color();
+        }
+        Self::teal()
+    }
+}
+
+impl Theme {
+    pub const BUILTIN: [&str; 5] = ["teal", "dark", "light", "high-contrast", "no-color"];
+    pub const NO_COLOR_VAR: &str = "NO_COLOR";
+
+    ///Finds the theme to use: a built-in name, a theme file, or the default one
+    pub fn load(name: Option<&str>) -> Result<Self, ConfigError> {
+        match name {
+            Some(name) => match Self::builtin(name) {
+                Some(theme) => Ok(theme),
+                None => Self::read_from_file(name),
+            },
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "teal" => Some(Self::teal()),
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "no-color" => Some(Self::no_color()),
+            _ => None,
+        }
+    }
+
+    fn read_from_file(path: &str) -> Result<Self, ConfigError> {
+        let buff = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&buff)?)
+    }
+
+    //---- Built-in themes
+
+    pub fn teal() -> Self {
+        Theme {
+            title_fg: Color::Reset,
+            title_bg: TEAL.c500,
+            header_fg: TEAL.c100,
+            header_bg: TEAL.c800,
+            row_bg: TEAL.c900,
+            alt_row_bg: TEAL.c800,
+            text_fg: TEAL.c200,
+            label_fg: Color::Red,
+            selected_bg: TEAL.c600,
+            marked_bg: TEAL.c700,
+            edit_fg: AMBER.c100,
+            edit_bg: AMBER.c700,
+            edit_value_bg: AMBER.c500,
+            footer_fg: AMBER.c700,
+            footer_bg: AMBER.c100,
+            priority_low_fg: Color::Reset,
+            priority_medium_fg: Color::Reset,
+            priority_high_fg: AMBER.c100,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Theme {
+            title_fg: SLATE.c100,
+            title_bg: SLATE.c700,
+            header_fg: SLATE.c200,
+            header_bg: SLATE.c800,
+            row_bg: SLATE.c950,
+            alt_row_bg: SLATE.c900,
+            text_fg: SLATE.c200,
+            label_fg: BLUE.c400,
+            selected_bg: BLUE.c800,
+            marked_bg: BLUE.c950,
+            edit_fg: AMBER.c100,
+            edit_bg: AMBER.c800,
+            edit_value_bg: AMBER.c600,
+            footer_fg: SLATE.c200,
+            footer_bg: SLATE.c800,
+            priority_low_fg: SLATE.c400,
+            priority_medium_fg: SLATE.c100,
+            priority_high_fg: AMBER.c300,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            title_fg: SLATE.c900,
+            title_bg: BLUE.c200,
+            header_fg: SLATE.c900,
+            header_bg: SLATE.c200,
+            row_bg: SLATE.c50,
+            alt_row_bg: SLATE.c100,
+            text_fg: SLATE.c800,
+            label_fg: BLUE.c700,
+            selected_bg: BLUE.c200,
+            marked_bg: AMBER.c100,
+            edit_fg: SLATE.c900,
+            edit_bg: AMBER.c200,
+            edit_value_bg: AMBER.c300,
+            footer_fg: SLATE.c700,
+            footer_bg: SLATE.c200,
+            priority_low_fg: SLATE.c500,
+            priority_medium_fg: SLATE.c900,
+            priority_high_fg: Color::Red,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Theme {
+            title_fg: Color::Black,
+            title_bg: Color::White,
+            header_fg: Color::White,
+            header_bg: Color::Black,
+            row_bg: Color::Black,
+            alt_row_bg: Color::Black,
+            text_fg: Color::White,
+            label_fg: Color::Yellow,
+            selected_bg: Color::Blue,
+            marked_bg: Color::Magenta,
+            edit_fg: Color::Black,
+            edit_bg: Color::Yellow,
+            edit_value_bg: Color::White,
+            footer_fg: Color::Black,
+            footer_bg: Color::White,
+            priority_low_fg: Color::White,
+            priority_medium_fg: Color::LightCyan,
+            priority_high_fg: Color::LightRed,
+        }
+    }
+
+    ///Uses the terminal colors only, the selection stays visible with the highlight symbol
+    pub fn no_color() -> Self {
+        Theme {
+            title_fg: Color::Reset,
+            title_bg: Color::Reset,
+            header_fg: Color::Reset,
+            header_bg: Color::Reset,
+            row_bg: Color::Reset,
+            alt_row_bg: Color::Reset,
+            text_fg: Color::Reset,
+            label_fg: Color::Reset,
+            selected_bg: Color::Reset,
+            marked_bg: Color::Reset,
+            edit_fg: Color::Reset,
+            edit_bg: Color::Reset,
+            edit_value_bg: Color::Reset,
+            footer_fg: Color::Reset,
+            footer_bg: Color::Reset,
+            priority_low_fg: Color::Reset,
+            priority_medium_fg: Color::Reset,
+            priority_high_fg: Color::Reset,
+        }
+    }
+
+    //---- Styles
+
+    pub fn header_style(&self) -> Style {
+        Style::new().fg(self.header_fg).bg(self.header_bg)
+    }
+
+    pub fn text_style(&self) -> Style {
+        Style::new().fg(self.text_fg)
+    }
+
+    pub fn selected_style(&self) -> Style {
+        Style::new().bg(self.selected_bg).add_modifier(Modifier::BOLD)
+    }
+
+    pub fn edit_style(&self) -> Style {
+        Style::new().bg(self.edit_bg).add_modifier(Modifier::BOLD).fg(self.edit_fg)
+    }
+
+    pub fn edit_value_style(&self) -> Style {
+        Style::new().bg(self.edit_value_bg).add_modifier(Modifier::BOLD).fg(self.edit_fg)
+    }
+
+    ///Background of the i-th row of a list
+    pub fn row_color(&self, i: usize) -> Color {
+        if i.is_multiple_of(2) {
+            self.row_bg
+        } else {
+            self.alt_row_bg
+        }
+    }
+
+    ///Style of a task name depending on its priority
+    pub fn priority_style(&self, priority: u8) -> Style {
+        self.tier_style(Tier::of(priority))
+    }
+
+    pub fn tier_style(&self, tier: Tier) -> Style {
+        match tier {
+            Tier::Low => Style::new().fg(self.priority_low_fg),
+            Tier::Medium => Style::new().fg(self.priority_medium_fg),
+            Tier::High => Style::new().fg(self.priority_high_fg).add_modifier(Modifier::BOLD),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_themes_exist() {
+        for name in Theme::BUILTIN {
+            assert!(Theme::builtin(name).is_some());
+        }
+        assert_eq!(Theme::load(Some("light")).unwrap(), Theme::light());
+    }
+
+    #[test]
+    fn partial_theme_file_uses_defaults() {
+        let path = "./test_theme.json";
+        fs::write(path, r##"{"row_bg": "#000000", "selected_bg": "blue"}"##).unwrap();
+        let theme = Theme::load(Some(path)).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(theme.row_bg, Color::Rgb(0, 0, 0));
+        assert_eq!(theme.selected_bg, Color::Blue);
+        assert_eq!(theme.header_bg, Theme::default().header_bg);
+    }
+
+    #[test]
+    fn missing_theme_file_is_an_error() {
+        assert!(Theme::load(Some("./no_such_theme.json")).is_err());
+    }
+}
@@ -1,49 +1,371 @@
+use chrono::{DateTime, Months, NaiveDate, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
 use std::io::{Write, Read};
 use std::env;
+use std::path::{Path, PathBuf};
 use colored::*;
 use thiserror::Error;
 
+use crate::crypto::{self, CryptoError};
+use crate::history::{self, Change, ChangeKind};
+use crate::oplog::{self, State};
+use crate::project;
+use crate::schema::{self, SchemaError};
+use crate::sync::{self, SyncError};
+
 //==== Task
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 ///Represents a task with a name, a priority and a state
 pub struct Task{
+    ///Stable identifier, given by the list when the task is added
+    #[serde(default)]
+    pub id:u64,
     pub name:String,
     pub priority:u8,
     pub done:bool,
+    #[serde(default)]
+    pub created:Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub due:Option<NaiveDate>,
+    #[serde(default)]
+    pub tags:Vec<String>,
+    ///When the task was last marked as done
+    #[serde(default)]
+    pub completed:Option<DateTime<Utc>>,
+    ///Workflow state, none means the first state or the last one if the task is done
+    #[serde(default)]
+    pub state:Option<String>,
+    ///Ids of the tasks that must be done before this one
+    #[serde(default)]
+    pub depends_on:Vec<u64>,
+    ///Periods of time spent on the task, the last one may still be running
+    #[serde(default)]
+    pub time_entries:Vec<TimeEntry>,
+    ///End of each focus cycle completed on the task
+    #[serde(default)]
+    pub pomodoros:Vec<DateTime<Utc>>,
+    ///When to be reminded of the task
+    #[serde(default)]
+    pub remind_at:Option<DateTime<Utc>>,
+    ///Person the task is assigned to
+    #[serde(default)]
+    pub assignee:Option<String>,
+    ///Once done, a new occurrence of the task is added
+    #[serde(default)]
+    pub recurrence:Option<Recurrence>,
+    ///Changes made to the task, oldest first
+    #[serde(default)]
+    pub history:Vec<Change>,
+    ///File of the task in an aggregated list and its id there, 0 until it is saved
+    #[serde(skip)]
+    pub source:Option<(String,u64)>,
 }
 
 impl Task{
-    pub fn new(name:&String, priority:u8) -> Self {
+    pub fn new(name:&str, priority:u8) -> Self {
         Self{
-            name:name.clone(),
-            priority:priority,
-            done:false
+            id:0,
+            name:name.to_string(),
+            priority,
+            done:false,
+            created:Some(Utc::now()),
+            due:None,
+            tags:vec!(),
+            completed:None,
+            state:None,
+            depends_on:vec!(),
+            time_entries:vec!(),
+            pomodoros:vec!(),
+            remind_at:None,
+            assignee:None,
+            recurrence:None,
+            history:vec!(),
+            source:None,
+        }
+    }
+
+    pub fn set_done(&mut self, done:bool){
+        if done && !self.done {
+            self.completed = Some(Utc::now());
+        } else if !done {
+            self.completed = None;
+        }
+        self.done = done;
+        self.state = None;
+    }
+
+    ///The task is assigned to this person, names are compared ignoring case
+    pub fn is_assigned_to(&self, user:&str) -> bool{
+        self.assignee.as_ref().is_some_and(|assignee| assignee.eq_ignore_ascii_case(user))
+    }
+
+    ///Start of the running timer, if any
+    pub fn running_since(&self) -> Option<DateTime<Utc>>{
+        self.time_entries.last().filter(|entry| entry.end.is_none()).map(|entry| entry.start)
+    }
+
+    //Ends the running timer of the task, if any
+    fn stop_timer(&mut self, now:DateTime<Utc>){
+        if let Some(entry) = self.time_entries.last_mut().filter(|entry| entry.end.is_none()) {
+            entry.end = Some(now);
         }
     }
 
+    ///Total time spent on the task, the running timer counts until now
+    pub fn tracked(&self, now:DateTime<Utc>) -> TimeDelta{
+        self.time_entries.iter().map(|entry| entry.duration(now)).sum()
+    }
+
     ///Returns a colored string representing the task
     pub fn to_formated_string(&self) -> ColoredString{
         let mut displayed_name = self.name.normal();
         if self.done {
             return displayed_name.strikethrough();
         }
-        displayed_name = match self.priority{
-            0..=2 => displayed_name,
-            3..=6 => displayed_name.red(),
-            _ => displayed_name.red().bold(),
+        displayed_name = match Tier::of(self.priority){
+            Tier::Low => displayed_name,
+            Tier::Medium => displayed_name.red(),
+            Tier::High => displayed_name.red().bold(),
         };
         displayed_name
     }
 }
 
+//==== Tier
+
+///Priority tiers, each shown with its own colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier{
+    Low,
+    Medium,
+    High,
+}
+
+impl Tier{
+    pub const ALL: [Tier;3] = [Tier::Low, Tier::Medium, Tier::High];
+
+    pub fn of(priority:u8) -> Self{
+        match priority{
+            0..=2 => Tier::Low,
+            3..=6 => Tier::Medium,
+            _ => Tier::High,
+        }
+    }
+
+    pub fn label(&self) -> &'static str{
+        match self {
+            Tier::Low => "low",
+            Tier::Medium => "medium",
+            Tier::High => "high",
+        }
+    }
+}
+
+//==== Recurrence
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Recurrence{
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Recurrence{
+    pub fn parse(text:&str) -> Option<Self>{
+        match text.to_lowercase().as_str() {
+            "day" | "daily" => Some(Recurrence::Daily),
+            "week" | "weekly" => Some(Recurrence::Weekly),
+            "month" | "monthly" => Some(Recurrence::Monthly),
+            "year" | "yearly" => Some(Recurrence::Yearly),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str{
+        match self {
+            Recurrence::Daily => "every day",
+            Recurrence::Weekly => "every week",
+            Recurrence::Monthly => "every month",
+            Recurrence::Yearly => "every year",
+        }
+    }
+
+    ///Date of the occurrence following the one on the given date
+    pub fn next(&self, date:NaiveDate) -> NaiveDate{
+        match self {
+            Recurrence::Daily => date + TimeDelta::days(1),
+            Recurrence::Weekly => date + TimeDelta::weeks(1),
+            Recurrence::Monthly => date + Months::new(1),
+            Recurrence::Yearly => date + Months::new(12),
+        }
+    }
+}
+
+//==== TimeEntry
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+///Period of time spent on a task
+pub struct TimeEntry{
+    pub start:DateTime<Utc>,
+    pub end:Option<DateTime<Utc>>,
+}
+
+impl TimeEntry{
+    pub fn duration(&self, now:DateTime<Utc>) -> TimeDelta{
+        self.end.unwrap_or(now) - self.start
+    }
+}
+
+//==== Workflow
+
+///Ordered states a task goes through, the last one means done
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Workflow{
+    states:Vec<String>,
+}
+
+impl Default for Workflow{
+    fn default() -> Self {
+        Workflow::new(&["Backlog", "In Progress", "Blocked", "Done"]).unwrap()
+    }
+}
+
+impl Workflow{
+    ///Creates a workflow, at least two distinct states are needed
+    pub fn new(states:&[&str]) -> Option<Self> {
+        let workflow = Workflow{ states:states.iter().map(|s| s.to_string()).collect() };
+        workflow.is_valid().then_some(workflow)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let mut states = self.states.clone();
+        states.sort();
+        states.dedup();
+        states.len() >= 2 && states.len() == self.states.len()
+    }
+
+    pub fn states(&self) -> &Vec<String>{
+        &self.states
+    }
+
+    ///Index of the state of a task, unknown states count as the first one
+    pub fn state_of(&self, task:&Task) -> usize {
+        let known = task.state.as_ref().and_then(|state| self.states.iter().position(|s| s == state));
+        match known {
+            Some(i) => i,
+            None if task.done => self.states.len() - 1,
+            None => 0,
+        }
+    }
+}
+
+//==== TrashedTask
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+///A removed task, kept until the trash is emptied
+pub struct TrashedTask{
+    pub task:Task,
+    pub deleted:DateTime<Utc>,
+}
+
+//==== SortMode
+
+///Order of the tasks, done tasks are always last except in manual mode
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode{
+    #[default]
+    Priority,
+    Due,
+    Created,
+    Alphabetical,
+    Manual,
+}
+
+impl SortMode{
+    pub fn next(&self) -> Self {
+        match self {
+            SortMode::Priority => SortMode::Due,
+            SortMode::Due => SortMode::Created,
+            SortMode::Created => SortMode::Alphabetical,
+            SortMode::Alphabetical => SortMode::Manual,
+            SortMode::Manual => SortMode::Priority,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortMode::Priority => "priority",
+            SortMode::Due => "due date",
+            SortMode::Created => "creation date",
+            SortMode::Alphabetical => "name",
+            SortMode::Manual => "manual",
+        }
+    }
+
+    fn compare(&self, a:&Task, b:&Task) -> Ordering {
+        match self {
+            SortMode::Priority | SortMode::Manual => b.priority.cmp(&a.priority),
+            //Tasks without due date come last
+            SortMode::Due => match (a.due, b.due) {
+                (Some(a_due), Some(b_due)) => a_due.cmp(&b_due),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }.then_with(|| b.priority.cmp(&a.priority)),
+            SortMode::Created => a.created.cmp(&b.created).then_with(|| a.id.cmp(&b.id)),
+            SortMode::Alphabetical => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        }
+    }
+}
+
 //==== Todo
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Todo{
-    list: Vec<Task>
+    ///Version of the file format, see `schema`
+    #[serde(default)]
+    version: u64,
+    #[serde(default)]
+    sort: SortMode,
+    #[serde(default)]
+    next_id: u64,
+    list: Vec<Task>,
+    #[serde(default)]
+    trash: Vec<TrashedTask>,
+    ///Saved filter queries, by name
+    #[serde(default)]
+    views: BTreeMap<String,String>,
+    ///Tasks as last read from the operation log, changes are recorded against them
+    #[serde(skip)]
+    log: Option<State>,
+    ///Tasks as last loaded or recorded in the history, by id, the history records the changes made since
+    #[serde(skip)]
+    saved: BTreeMap<u64,Value>,
+    ///Ids of the tasks in the trash when last loaded or saved
+    #[serde(skip)]
+    saved_trash: HashSet<u64>,
+    ///Lists an aggregated list was made of, by path, their tasks are in this list
+    #[serde(skip)]
+    sources: Vec<(String,Todo)>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DependencyError {
+    #[error("No task with id {0}")]
+    UnknownTask(u64),
+    #[error("A task can't depend on itself")]
+    SelfDependency,
+    #[error("Task {0} already depends on this task")]
+    Cycle(u64),
 }
 
 #[derive(Debug, Error)]
@@ -52,16 +374,32 @@ pub enum TodoFileError {
     IoError(#[from] std::io::Error),
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+    #[error("Sync error: {0}")]
+    Sync(#[from] SyncError),
+    #[error("Unsupported file: {0}")]
+    Schema(#[from] SchemaError),
+    #[error("Encryption error: {0}")]
+    Crypto(#[from] CryptoError),
 }
 
 impl Todo{
+    ///List of the current directory from before project lists, still used when it exists
     pub const DEFAULT_PATH: &str = "./tasks.json";
     pub const PATH_VAR: &str = "TODO_PATH";
 
 
     pub fn new() -> Self {
         Todo{
-            list:vec!()
+            version:schema::VERSION,
+            sort:SortMode::default(),
+            next_id:1,
+            list:vec!(),
+            trash:vec!(),
+            views:BTreeMap::new(),
+            log:None,
+            saved:BTreeMap::new(),
+            saved_trash:HashSet::new(),
+            sources:vec!(),
         }
     }
 
@@ -73,39 +411,320 @@ impl Todo{
         &self.list[i]
     }
 
+    ///Returns the index of the task with the given id
+    pub fn position(&self, id:u64)->Option<usize>{
+        self.list.iter().position(|task| task.id == id)
+    }
+
+    pub fn sort_mode(&self) -> SortMode{
+        self.sort
+    }
+
+    pub fn set_sort_mode(&mut self, sort:SortMode){
+        self.sort = sort;
+        self.sort_list();
+    }
+
+    ///Moves a task one place up or down and switches to manual order,
+    ///returns the new index of the task
+    pub fn move_task(&mut self, index:usize, up:bool) -> Option<usize>{
+        if !self.move_tasks(&[index], up) {
+            return None;
+        }
+        Some(if up { index - 1 } else { index + 1 })
+    }
+
+    ///Moves several tasks one place up or down together and switches to manual order,
+    ///returns false if a task is already at the end of the list
+    pub fn move_tasks(&mut self, index:&[usize], up:bool) -> bool{
+        let mut indexes = index.to_vec();
+        indexes.sort();
+        indexes.dedup();
+        let (Some(first), Some(last)) = (indexes.first(), indexes.last()) else {
+            return false;
+        };
+        if *last >= self.list.len() || (up && *first == 0) || (!up && *last + 1 == self.list.len()) {
+            return false;
+        }
+        self.sort = SortMode::Manual;
+        if up {
+            for i in indexes {
+                self.list.swap(i, i - 1);
+            }
+        } else {
+            for i in indexes.into_iter().rev() {
+                self.list.swap(i, i + 1);
+            }
+        }
+        true
+    }
+
+    ///Loads the list, tasks are rebuilt from the operation log when it is enabled
     pub fn load() -> Result<Self,TodoFileError>{
         let path = Self::load_path();
-        Self::read_from_file(path.as_str())
+        let mut todo = Self::read_from_file(path.as_str())?;
+        if let Some(state) = oplog::load()? {
+            todo.apply_log(state)?;
+        }
+        todo.mark_saved()?;
+        Ok(todo)
+    }
+
+    ///Loads several files as one list, saving it writes each task back to the file it came from.
+    ///Tasks get new ids, the views and sort order are the ones of the first file
+    pub fn load_all(paths:&[String]) -> Result<Self,TodoFileError>{
+        let mut all = Todo::new();
+        for path in paths {
+            let mut source = Self::read_from_file(path)?;
+            source.mark_saved()?;
+            if all.sources.is_empty() {
+                all.sort = source.sort;
+                all.views = source.views.clone();
+            }
+            let mut tasks = std::mem::take(&mut source.list);
+            let mut trash = std::mem::take(&mut source.trash);
+            //Ids of the file to ids of the aggregated list
+            let mut ids = BTreeMap::new();
+            for task in tasks.iter_mut().chain(trash.iter_mut().map(|trashed| &mut trashed.task)) {
+                ids.insert(task.id, all.next_id);
+                task.source = Some((path.clone(), task.id));
+                task.id = all.next_id;
+                all.next_id += 1;
+            }
+            for task in tasks.iter_mut().chain(trash.iter_mut().map(|trashed| &mut trashed.task)) {
+                task.depends_on = task.depends_on.iter().filter_map(|id| ids.get(id).copied()).collect();
+            }
+            all.list.extend(tasks);
+            all.trash.extend(trash);
+            all.sources.push((path.clone(), source));
+        }
+        all.sort_list();
+        all.mark_saved()?;
+        Ok(all)
+    }
+
+    ///Files of an aggregated list, empty for a single file
+    pub fn sources(&self) -> Vec<&str>{
+        self.sources.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    ///Index of the file of a task in an aggregated list, new tasks go to the first one
+    pub fn source_index(&self, task:&Task) -> usize{
+        task.source.as_ref()
+            .and_then(|(path, _)| self.sources.iter().position(|(source, _)| source == path))
+            .unwrap_or(0)
+    }
+
+    pub fn add(&mut self, name:&str, priority:u8){
+        self.push(Task::new(name,priority));
+        self.record_history(Utc::now());
     }
 
-    pub fn add(&mut self, name:&String, priority:u8){
-        self.list.push(Task::new(name,if priority> 10 {10} else {priority}));
+    ///Adds a task built elsewhere at its sorted place, returns the id it was given
+    pub fn insert(&mut self, task:Task) -> u64{
+        let id = self.push(task);
+        self.sort_list();
+        self.record_history(Utc::now());
+        id
     }
 
     pub fn done(&mut self, index:usize){
         if index >= self.list.len() {
             return;
         }
-        self.list[index].done = !self.list[index].done;
+        let done = !self.list[index].done;
+        self.list[index].set_done(done);
+        self.renew(index);
+        self.sort_list();
+        self.record_history(Utc::now());
+    }
+
+    pub fn set_done(&mut self, index:usize, done:bool){
+        if index >= self.list.len() {
+            return;
+        }
+        self.list[index].set_done(done);
+        self.renew(index);
+        self.sort_list();
+        self.record_history(Utc::now());
+    }
+
+    ///Moves a task to a state of the workflow, the last state marks it as done
+    pub fn set_state(&mut self, index:usize, workflow:&Workflow, state:usize){
+        if index >= self.list.len() || state >= workflow.states().len() {
+            return;
+        }
+        let task = &mut self.list[index];
+        task.set_done(state + 1 == workflow.states().len());
+        task.state = Some(workflow.states()[state].clone());
+        self.renew(index);
+        self.sort_list();
+        self.record_history(Utc::now());
+    }
+
+    ///Replaces the dependencies of a task, nothing is changed if one of them is invalid
+    pub fn set_dependencies(&mut self, index:usize, ids:&[u64]) -> Result<(),DependencyError>{
+        if index >= self.list.len() {
+            return Ok(());
+        }
+        let id = self.list[index].id;
+        for dependency in ids {
+            if *dependency == id {
+                return Err(DependencyError::SelfDependency);
+            }
+            if self.position(*dependency).is_none() {
+                return Err(DependencyError::UnknownTask(*dependency));
+            }
+            if self.depends_on(*dependency, id) {
+                return Err(DependencyError::Cycle(*dependency));
+            }
+        }
+        let mut ids = ids.to_vec();
+        ids.sort();
+        ids.dedup();
+        self.list[index].depends_on = ids;
+        self.record_history(Utc::now());
+        Ok(())
+    }
+
+    ///Returns true while one of the dependencies of the task is not done
+    pub fn is_blocked(&self, task:&Task) -> bool{
+        task.depends_on.iter()
+            .filter_map(|id| self.position(*id))
+            .any(|i| !self.list[i].done)
+    }
+
+    ///Returns true if the task is open and not blocked
+    pub fn is_ready(&self, index:usize) -> bool{
+        index < self.list.len() && !self.list[index].done && !self.is_blocked(&self.list[index])
+    }
+
+    ///Starts the timer of a task, the running timer of another task is stopped
+    pub fn start_timer(&mut self, index:usize){
+        if index >= self.list.len() || self.list[index].running_since().is_some() {
+            return;
+        }
+        self.stop_timer();
+        self.list[index].time_entries.push(TimeEntry{ start:Utc::now(), end:None });
+    }
+
+    ///Stops the running timer, returns the index of its task
+    pub fn stop_timer(&mut self) -> Option<usize>{
+        let index = self.running_timer()?;
+        self.list[index].stop_timer(Utc::now());
+        Some(index)
+    }
+
+    ///Index of the task with a running timer, only one can run at a time
+    pub fn running_timer(&self) -> Option<usize>{
+        self.list.iter().position(|task| task.running_since().is_some())
+    }
+
+    pub fn set_due(&mut self, index:usize, due:Option<NaiveDate>){
+        if index >= self.list.len() {
+            return;
+        }
+        self.list[index].due = due;
+        self.sort_list();
+        self.record_history(Utc::now());
+    }
+
+    pub fn set_reminder(&mut self, index:usize, at:Option<DateTime<Utc>>){
+        if index < self.list.len() {
+            self.list[index].remind_at = at;
+            self.record_history(Utc::now());
+        }
+    }
+
+    ///Changes several fields of a task at once
+    pub fn update(&mut self, index:usize, change:impl FnOnce(&mut Task)){
+        if index >= self.list.len() {
+            return;
+        }
+        change(&mut self.list[index]);
+        self.list[index].priority = self.list[index].priority.min(10);
         self.sort_list();
+        self.record_history(Utc::now());
     }
 
-    pub fn remove(&mut self, index:&Vec<usize>) -> Result<(),()> {
-        let mut indexes = index.clone();
+    ///Logs a finished focus cycle on a task
+    pub fn log_pomodoro(&mut self, index:usize, at:DateTime<Utc>){
+        if index < self.list.len() {
+            self.list[index].pomodoros.push(at);
+        }
+    }
+
+    ///Adds a tag to a task, a tag already present is not duplicated
+    pub fn add_tag(&mut self, index:usize, tag:&str){
+        let tag = tag.trim().trim_start_matches('#');
+        if index >= self.list.len() || tag.is_empty() {
+            return;
+        }
+        let tags = &mut self.list[index].tags;
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+            self.record_history(Utc::now());
+        }
+    }
+
+    pub fn remove(&mut self, index:&[usize]) -> Result<(),()> {
+        let mut indexes = index.to_vec();
         indexes.sort();
         indexes.dedup();
         for i in indexes.iter().rev() {
             if *i >= self.list.len() {
                 return Err(());
             }
-            self.list.remove(*i);
+            //A trashed task is not worked on anymore
+            let mut task = self.list.remove(*i);
+            task.stop_timer(Utc::now());
+            self.trash.push(TrashedTask{ task, deleted:Utc::now() });
         }
         self.sort_list();
+        self.record_history(Utc::now());
         Ok(())
     }
 
+    ///Saved filter queries, by name
+    pub fn views(&self) -> &BTreeMap<String,String>{
+        &self.views
+    }
+
+    ///Saves a filter query under a name, replacing the view with the same name
+    pub fn save_view(&mut self, name:&str, query:&str){
+        self.views.insert(name.trim().to_string(), query.trim().to_string());
+    }
+
+    ///Returns false if there was no view with this name
+    pub fn remove_view(&mut self, name:&str) -> bool{
+        self.views.remove(name).is_some()
+    }
+
+    pub fn trash(&self) -> &Vec<TrashedTask>{
+        &self.trash
+    }
+
+    ///Puts a task from the trash back in the list, returns its index in the list
+    pub fn restore(&mut self, trash_index:usize) -> Result<usize,()> {
+        if trash_index >= self.trash.len() {
+            return Err(());
+        }
+        let mut task = self.trash.remove(trash_index).task;
+        //Tasks trashed by older versions could keep their timer running
+        task.stop_timer(Utc::now());
+        let id = task.id;
+        self.list.push(task);
+        self.sort_list();
+        self.record_history(Utc::now());
+        self.position(id).ok_or(())
+    }
+
+    pub fn empty_trash(&mut self){
+        self.trash.clear();
+    }
+
     pub fn list(&self){
-        if self.list.len() == 0 {
+        if self.list.is_empty() {
             println!("[Empty list]");
             return;
         }
@@ -119,11 +738,12 @@ impl Todo{
         self.list = vec!();
     }
 
-    pub fn rename(&mut self, index:usize, name:&String){
+    pub fn rename(&mut self, index:usize, name:&str){
         if index >= self.list.len() {
             return;
         }
-        self.list[index].name = name.clone();
+        self.list[index].name = name.to_string();
+        self.record_history(Utc::now());
     }
 
     pub fn set_priority(&mut self, index:usize, priority:u8){
@@ -132,15 +752,25 @@ impl Todo{
         }
         self.list[index].priority = priority;
         self.sort_list();
+        self.record_history(Utc::now());
     }
 
+    ///Saves the list, encrypted when a secret is set, unless the file was written by a newer version
     pub fn save_to(&self, path:String) -> Result<(), TodoFileError> {
-        //let path = Self::load_path();
+        if let Ok(text) = fs::read_to_string(&path) {
+            if let Ok(existing) = serde_json::from_str(&crypto::open(text)?) {
+                schema::check(&existing)?;
+            }
+        }
+        //The folder of the global list is made on first save
+        if let Some(dir) = Path::new(&path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
         // Create/open the file
         let mut f = File::create(path)?;
 
         // Serialize the struct
-        let serialized = serde_json::to_string(&self)?;
+        let serialized = crypto::seal(&serde_json::to_string(&self)?)?;
 
         // Write to file
         f.write_all(serialized.as_bytes())?;
@@ -148,34 +778,217 @@ impl Todo{
         Ok(())
     }
 
-    pub fn save(&self) -> Result<(), TodoFileError> {
+    ///Saves the list, then commits it when sync is enabled.
+    ///With the operation log, changes are then logged and the list saved again with the tasks of other replicas.
+    ///An aggregated list is saved to the files it was made of
+    pub fn save(&mut self) -> Result<(), TodoFileError> {
+        if !self.sources.is_empty() {
+            return self.save_sources();
+        }
         let path = Self::load_path();
-        self.save_to(path)
+        self.record_history(Utc::now());
+        //The file is written first so the changes are kept when the log can't be written
+        self.write(path.clone())?;
+        if oplog::record(self)? {
+            self.write(path)?;
+        }
+        Ok(())
+    }
+
+    ///Replaces the tasks and the trash by the ones rebuilt from the operation log
+    pub fn apply_log(&mut self, state:State) -> Result<(), TodoFileError> {
+        self.list = state.list.iter().cloned().map(serde_json::from_value).collect::<Result<_,_>>()?;
+        self.trash = state.trash.iter().cloned().map(serde_json::from_value).collect::<Result<_,_>>()?;
+        self.assign_ids();
+        self.sort_list();
+        self.log = Some(state);
+        //The changes of the other replicas come with their own history
+        self.mark_saved()
+    }
+
+    pub fn log_state(&self) -> Option<&State>{
+        self.log.as_ref()
     }
 
+    ///$TODO_PATH, else the `.todo` list of the current directory or of a parent,
+    ///else ./tasks.json if it exists, else the global list
     pub fn load_path() -> String {
-        match env::var(Self::PATH_VAR) {
-            Ok(val) => val,
-            Err(_e) => Self::DEFAULT_PATH.to_string()
+        if let Ok(val) = env::var(Self::PATH_VAR) {
+            return val;
+        }
+        let project = env::current_dir().ok().and_then(|dir| project::find(&dir));
+        let legacy = Some(PathBuf::from(Self::DEFAULT_PATH)).filter(|path| path.is_file());
+        match project.or(legacy).or_else(project::global_path) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => Self::DEFAULT_PATH.to_string()
         }
     }
 
 // ---- Private
+    //Saves the list to a file, then commits it when sync is enabled
+    fn write(&mut self, path:String) -> Result<(), TodoFileError> {
+        self.save_to(path.clone())?;
+        self.mark_saved()?;
+        sync::commit(&path)?;
+        Ok(())
+    }
+
+    //Writes each task of an aggregated list back to its file, new tasks go to the first file.
+    //Dependencies between files can't be saved and are left out
+    fn save_sources(&mut self) -> Result<(), TodoFileError> {
+        self.record_history(Utc::now());
+        let mut sources = std::mem::take(&mut self.sources);
+        //Ids of the aggregated list to the index of the file and the id there
+        let mut ids = BTreeMap::new();
+        for task in self.list.iter_mut().chain(self.trash.iter_mut().map(|trashed| &mut trashed.task)) {
+            let (path, id) = task.source.get_or_insert_with(|| (sources[0].0.clone(), 0));
+            let i = sources.iter().position(|(source, _)| source == path).unwrap_or(0);
+            if *id == 0 {
+                *id = sources[i].1.next_id;
+                sources[i].1.next_id += 1;
+            }
+            ids.insert(task.id, (i, *id));
+        }
+        let in_file = |task:&Task, i:usize| {
+            let mut task = task.clone();
+            task.id = ids[&task.id].1;
+            task.depends_on = task.depends_on.iter().filter_map(|id| ids.get(id)).filter(|(j, _)| *j == i).map(|(_, id)| *id).collect();
+            task.source = None;
+            task
+        };
+        let mut result = Ok(());
+        for (i, (path, source)) in sources.iter_mut().enumerate() {
+            source.list = self.list.iter().filter(|task| ids[&task.id].0 == i).map(|task| in_file(task, i)).collect();
+            source.trash = self.trash.iter()
+                .filter(|trashed| ids[&trashed.task.id].0 == i)
+                .map(|trashed| TrashedTask{ task:in_file(&trashed.task, i), deleted:trashed.deleted })
+                .collect();
+            if i == 0 {
+                source.sort = self.sort;
+                source.views = self.views.clone();
+            }
+            //The other files are saved even when one fails
+            if let Err(e) = source.write(path.clone()) {
+                result = result.and(Err(e));
+            }
+        }
+        self.sources = sources;
+        self.mark_saved()?;
+        result
+    }
+
+    //Adds the changes made since they were last recorded to the history of the tasks.
+    //Each change of the list records its own, so they keep their time and intermediate states are kept
+    fn record_history(&mut self, now:DateTime<Utc>){
+        let author = history::author();
+        let change = |kind| Change{ at:now, author:author.clone(), kind };
+        let mut recorded = BTreeMap::new();
+        for task in self.list.iter_mut() {
+            //The history is not compared, the task is kept without its new changes
+            let value = serde_json::to_value(&*task).unwrap_or_default();
+            let kinds = match self.saved.get(&task.id) {
+                Some(before) => history::changes(before, &value),
+                None if self.saved_trash.contains(&task.id) => vec!(ChangeKind::Restored),
+                None => vec!(ChangeKind::Created),
+            };
+            task.history.extend(kinds.into_iter().map(change));
+            recorded.insert(task.id, value);
+        }
+        for trashed in self.trash.iter_mut().filter(|trashed| self.saved.contains_key(&trashed.task.id)) {
+            trashed.task.history.push(change(ChangeKind::Removed));
+        }
+        self.saved = recorded;
+        self.saved_trash = self.trash.iter().map(|trashed| trashed.task.id).collect();
+    }
+
+    fn mark_saved(&mut self) -> Result<(), TodoFileError> {
+        self.saved = self.list.iter().map(|task| Ok((task.id, serde_json::to_value(task)?))).collect::<Result<_,serde_json::Error>>()?;
+        self.saved_trash = self.trash.iter().map(|trashed| trashed.task.id).collect();
+        Ok(())
+    }
+
+    fn push(&mut self, mut task:Task) -> u64{
+        task.priority = task.priority.min(10);
+        task.id = self.next_id;
+        self.next_id += 1;
+        self.list.push(task);
+        self.next_id - 1
+    }
+
+    //Adds the next occurrence of a recurring task that was just done,
+    //the recurrence moves to the new task so it is only renewed once
+    fn renew(&mut self, index:usize){
+        let task = &mut self.list[index];
+        if !task.done {
+            return;
+        }
+        let Some(recurrence) = task.recurrence.take() else {
+            return;
+        };
+        let mut next = Task::new(&task.name, task.priority);
+        next.due = Some(recurrence.next(task.due.unwrap_or(Utc::now().date_naive())));
+        next.tags = task.tags.clone();
+        next.assignee = task.assignee.clone();
+        next.recurrence = Some(recurrence);
+        next.source = task.source.as_ref().map(|(path, _)| (path.clone(), 0));
+        self.push(next);
+    }
+
+    //Returns true if the task depends on the other one, directly or not
+    fn depends_on(&self, id:u64, other:u64) -> bool{
+        let mut visited = HashSet::new();
+        let mut to_visit = vec![id];
+        while let Some(current) = to_visit.pop() {
+            if current == other {
+                return true;
+            }
+            if !visited.insert(current) {
+                continue;
+            }
+            if let Some(i) = self.position(current) {
+                to_visit.extend(&self.list[i].depends_on);
+            }
+        }
+        false
+    }
+
     fn sort_list(&mut self){
+        let sort = self.sort;
+        if sort == SortMode::Manual {
+            return;
+        }
         self.list.sort_by(|a, b| {
             if a.done != b.done {
                 a.done.cmp(&b.done)
             }else{
-                b.priority.cmp(&a.priority)
+                sort.compare(a, b)
             }
         });
     }
 
+    //Gives an id to tasks saved before ids existed
+    fn assign_ids(&mut self){
+        let max = self.list.iter()
+            .chain(self.trash.iter().map(|trashed| &trashed.task))
+            .map(|task| task.id)
+            .max()
+            .unwrap_or(0);
+        self.next_id = self.next_id.max(max + 1);
+        let trashed = self.trash.iter_mut().map(|trashed| &mut trashed.task);
+        for task in self.list.iter_mut().chain(trashed).filter(|task| task.id == 0) {
+            task.id = self.next_id;
+            self.next_id += 1;
+        }
+    }
+
     fn read_from_file(path: &str) -> Result<Todo, TodoFileError> {
         let mut file = File::open(path)?;
         let mut buff = String::new();
         file.read_to_string(&mut buff)?;
-        let todo: Todo = serde_json::from_str(&buff)?;
+        let mut value: Value = serde_json::from_str(&crypto::open(buff)?)?;
+        schema::upgrade(&mut value)?;
+        let mut todo: Todo = serde_json::from_value(value)?;
+        todo.assign_ids();
             /*.map_err(|e| io::Error::new(io::ErrorKind::InvalidData,
                 format!("Failed to parse JSON: {}", e)))?;*/
         Ok(todo)
@@ -191,21 +1004,29 @@ mod tests {
     use super::*;
 
 
+    #[test]
+    fn priority_tiers(){
+        assert_eq!(Tier::of(2),Tier::Low);
+        assert_eq!(Tier::of(6),Tier::Medium);
+        assert_eq!(Tier::of(7),Tier::High);
+        assert_eq!(Tier::of(10),Tier::High);
+    }
+
     #[test]
     fn new_task_feilds_are_correct(){
-        let task = Task::new(&"Test".to_string(),5);
+        let task = Task::new("Test",5);
         assert_eq!(task.name,"Test");
         assert_eq!(task.priority,5);
-        assert_eq!(task.done,false);
+        assert!(!task.done);
     }
 
     #[test]
     fn task_to_str_is_correct(){
-        assert_eq!(Task::new(&"Small".to_string(),1).to_formated_string(),
+        assert_eq!(Task::new("Small",1).to_formated_string(),
         "Small".normal());
-        assert_eq!(Task::new(&"Normal".to_string(),5).to_formated_string(),
+        assert_eq!(Task::new("Normal",5).to_formated_string(),
         "Normal".red());
-        assert_eq!(Task::new(&"Highest".to_string(),9).to_formated_string(),
+        assert_eq!(Task::new("Highest",9).to_formated_string(),
         "Highest".red().bold());
     }
     
@@ -218,41 +1039,40 @@ mod tests {
     #[test]
     fn add_task_increases_length() {
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
+        todo.add("Task1",2);
         assert_eq!(todo.list.len(), 1);
     }
 
     #[test]
     fn added_task_correct_info() {
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
+        todo.add("Task1",2);
         assert_eq!(todo.list[0].name,"Task1".to_string());
         assert_eq!(todo.list[0].priority,2);
-        assert_eq!(todo.list[0].done,false);
+        assert!(!todo.list[0].done);
     }
 
     #[test]
     fn rename_priority_and_done_updates(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
-        todo.rename(0,&"Task2".to_string());
+        todo.add("Task1",2);
+        todo.rename(0,"Task2");
         assert_eq!(todo.list[0].name,"Task2".to_string());
         todo.set_priority(0,5);
         assert_eq!(todo.list[0].priority,5);
         todo.done(0);
-        assert_eq!(todo.list[0].done,true);
+        assert!(todo.list[0].done);
     }
 
     #[test]
     fn serialize_and_deserialize_ok(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
+        todo.add("Task1",2);
         let save_path = "./test.json";
-        match todo.save_to(save_path.to_string()){
-            Err(_e)=> assert!(false),
-            _ => {}
-        };
-        let todo_read = Todo::read_from_file(&save_path).unwrap();
+        if let Err(e) = todo.save_to(save_path.to_string()) {
+            panic!("{}", e);
+        }
+        let todo_read = Todo::read_from_file(save_path).unwrap();
         assert_eq!(todo.list[0].name,todo_read.list[0].name);
         assert_eq!(todo.list[0].priority,todo_read.list[0].priority);
         assert_eq!(todo.list[0].done,todo_read.list[0].done);
@@ -261,9 +1081,9 @@ mod tests {
     #[test]
     fn remove_task(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
-        todo.add(&"Task2".to_string(),2);
-        assert_eq!(todo.remove(&vec!(0)),Ok(()));
+        todo.add("Task1",2);
+        todo.add("Task2",2);
+        assert_eq!(todo.remove(&[0]),Ok(()));
         assert_eq!(todo.list.len(),1);
         assert_eq!(todo.list[0].name,"Task2".to_string());
     }
@@ -271,16 +1091,92 @@ mod tests {
     #[test]
     fn remove_task_out_of_bounds(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
-        todo.add(&"Task2".to_string(),2);
-        assert_eq!(todo.remove(&vec!(0,2)),Err(()));
+        todo.add("Task1",2);
+        todo.add("Task2",2);
+        assert_eq!(todo.remove(&[0,2]),Err(()));
+    }
+
+    #[test]
+    fn removed_tasks_go_to_trash(){
+        let mut todo = Todo::new();
+        todo.add("Task1",2);
+        todo.add("Task2",5);
+        todo.remove(&[0,1]).unwrap();
+        assert_eq!(todo.trash().len(),2);
+        assert_eq!(todo.restore(1),Ok(0));
+        assert_eq!(todo.list[0].name,"Task1");
+        assert_eq!(todo.restore(3),Err(()));
+        todo.empty_trash();
+        assert!(todo.trash().is_empty());
+    }
+
+    #[test]
+    fn trash_is_saved(){
+        let mut todo = Todo::new();
+        todo.add("Task1",2);
+        todo.remove(&[0]).unwrap();
+        let json = serde_json::to_string(&todo).unwrap();
+        let read: Todo = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.trash()[0].task.name,"Task1");
+    }
+
+    #[test]
+    fn newer_files_are_not_overwritten(){
+        let path = "./test_newer_tasks.json";
+        let newer = format!(r#"{{"version":{},"list":[]}}"#, schema::VERSION+1);
+        fs::write(path, &newer).unwrap();
+        assert!(matches!(Todo::read_from_file(path), Err(TodoFileError::Schema(_))));
+        assert!(matches!(Todo::new().save_to(path.to_string()), Err(TodoFileError::Schema(_))));
+        assert_eq!(fs::read_to_string(path).unwrap(), newer);
+
+        fs::write(path, r#"{"list":[{"name":"Task1","priority":2,"done":false}]}"#).unwrap();
+        let todo = Todo::read_from_file(path).unwrap();
+        todo.save_to(path.to_string()).unwrap();
+        let saved:Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(saved["version"], schema::VERSION);
+        assert_eq!(todo.task(0).id, 1);
+    }
+
+    #[test]
+    fn aggregated_lists_are_saved_to_their_files(){
+        let a = "./test_aggregated_a.json".to_string();
+        let b = "./test_aggregated_b.json".to_string();
+        fs::write(&a, r#"{"list":[{"id":1,"name":"A1","priority":5,"done":false},{"id":2,"name":"A2","priority":3,"done":false,"depends_on":[1]}]}"#).unwrap();
+        fs::write(&b, r#"{"list":[{"id":1,"name":"B1","priority":4,"done":false}]}"#).unwrap();
+        let mut all = Todo::load_all(&[a.clone(), b.clone()]).unwrap();
+        assert_eq!(all.sources(), vec![a.as_str(), b.as_str()]);
+        let names:Vec<&str> = all.items().iter().map(|task| task.name.as_str()).collect();
+        assert_eq!(names, vec!["A1","B1","A2"]);
+        assert_eq!(all.task(2).depends_on, vec![all.task(0).id]);
+
+        all.done(1);
+        all.add("New in A", 1);
+        let mut task = Task::new("New in B", 1);
+        task.source = Some((b.clone(), 0));
+        all.insert(task);
+        all.save().unwrap();
+        all.save().unwrap();
+        let read = |path:&str| -> Value { serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap() };
+        let (saved_a, saved_b) = (read(&a), read(&b));
+        fs::remove_file(&a).unwrap();
+        fs::remove_file(&b).unwrap();
+
+        let names = |list:&Value| -> Vec<(String,u64)> {
+            list["list"].as_array().unwrap().iter().map(|task| (task["name"].as_str().unwrap().to_string(), task["id"].as_u64().unwrap())).collect()
+        };
+        assert_eq!(names(&saved_a), vec![("A1".to_string(),1),("A2".to_string(),2),("New in A".to_string(),3)]);
+        assert_eq!(saved_a["list"][1]["depends_on"], serde_json::json!([1]));
+        assert_eq!(names(&saved_b), vec![("New in B".to_string(),2),("B1".to_string(),1)]);
+        assert_eq!(saved_b["list"][1]["done"], true);
+        assert_eq!(saved_b["list"][1]["history"][0]["change"], "done");
     }
 
     #[test]
     fn clear_list(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
-        todo.add(&"Task2".to_string(),2);
+        todo.add("Task1",2);
+        todo.add("Task2",2);
         todo.clear();
         assert_eq!(todo.list.len(),0);
     }
@@ -288,9 +1184,9 @@ mod tests {
     #[test]
     fn sort_list(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
-        todo.add(&"Task2".to_string(),5);
-        todo.add(&"Task3".to_string(),1);
+        todo.add("Task1",2);
+        todo.add("Task2",5);
+        todo.add("Task3",1);
         todo.sort_list();
         assert_eq!(todo.list[0].name,"Task2".to_string());
         assert_eq!(todo.list[1].name,"Task1".to_string());
@@ -300,11 +1196,234 @@ mod tests {
     #[test]
     fn set_priority_out_of_bounds(){
         let mut todo = Todo::new();
-        todo.add(&"Task1".to_string(),2);
+        todo.add("Task1",2);
         todo.set_priority(1,5);
         assert_eq!(todo.list[0].priority,2);
     }
 
+    #[test]
+    fn sort_modes(){
+        let mut todo = Todo::new();
+        todo.add("banana",2);
+        todo.add("Apple",5);
+        todo.add("cherry",1);
+        todo.list[2].due = NaiveDate::from_ymd_opt(2024, 1, 1);
+        todo.set_sort_mode(SortMode::Alphabetical);
+        assert_eq!(todo.list[0].name,"Apple");
+        assert_eq!(todo.list[2].name,"cherry");
+        todo.set_sort_mode(SortMode::Due);
+        assert_eq!(todo.list[0].name,"cherry");
+        assert_eq!(todo.list[1].name,"Apple");
+        todo.set_sort_mode(SortMode::Created);
+        assert_eq!(todo.list[0].name,"banana");
+        todo.done(0);
+        assert_eq!(todo.list[2].name,"banana");
+    }
+
+    #[test]
+    fn manual_order(){
+        let mut todo = Todo::new();
+        todo.add("Task1",2);
+        todo.add("Task2",5);
+        assert_eq!(todo.move_task(0,false),Some(1));
+        assert_eq!(todo.sort_mode(),SortMode::Manual);
+        assert_eq!(todo.move_task(1,false),None);
+        todo.set_priority(1,9);
+        todo.done(0);
+        assert_eq!(todo.list[0].name,"Task2");
+        assert_eq!(todo.list[1].name,"Task1");
+    }
+
+    #[test]
+    fn move_several_tasks(){
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        todo.add("Task2",1);
+        todo.add("Task3",1);
+        todo.add("Task4",1);
+        assert!(todo.move_tasks(&[1,3],true));
+        let names: Vec<&str> = todo.list.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names,vec!["Task2","Task1","Task4","Task3"]);
+        assert!(!todo.move_tasks(&[0,2],true));
+        assert!(!todo.move_tasks(&[4],false));
+    }
+
+    #[test]
+    fn done_sets_completion_time(){
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        todo.done(0);
+        assert!(todo.list[0].completed.is_some());
+        todo.done(0);
+        assert!(todo.list[0].completed.is_none());
+    }
+
+    #[test]
+    fn workflow_states(){
+        let workflow = Workflow::default();
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        assert_eq!(workflow.state_of(&todo.list[0]),0);
+        todo.set_state(0,&workflow,1);
+        assert_eq!(todo.list[0].state.as_deref(),Some("In Progress"));
+        assert!(!todo.list[0].done);
+        todo.set_state(0,&workflow,3);
+        assert!(todo.list[0].done);
+        todo.done(0);
+        assert_eq!(workflow.state_of(&todo.list[0]),0);
+        todo.done(0);
+        assert_eq!(workflow.state_of(&todo.list[0]),3);
+        assert!(Workflow::new(&["Only"]).is_none());
+        assert!(Workflow::new(&["A","A"]).is_none());
+    }
+
+    #[test]
+    fn dependencies_block_tasks(){
+        let mut todo = Todo::new();
+        todo.add("Build",1);
+        todo.add("Test",1);
+        todo.add("Release",1);
+        assert_eq!(todo.set_dependencies(2,&[2,1,2]),Ok(()));
+        assert_eq!(todo.list[2].depends_on,vec!(1,2));
+        assert!(todo.is_blocked(&todo.list[2]));
+        assert!(!todo.is_ready(2));
+        assert!(todo.is_ready(0));
+        todo.set_done(0,true);
+        let release = todo.position(3).unwrap();
+        assert!(todo.is_blocked(&todo.list[release]));
+        todo.set_done(todo.position(2).unwrap(),true);
+        let release = todo.position(3).unwrap();
+        assert!(todo.is_ready(release));
+    }
+
+    #[test]
+    fn invalid_dependencies(){
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        todo.add("Task2",1);
+        todo.add("Task3",1);
+        assert_eq!(todo.set_dependencies(0,&[1]),Err(DependencyError::SelfDependency));
+        assert_eq!(todo.set_dependencies(0,&[9]),Err(DependencyError::UnknownTask(9)));
+        todo.set_dependencies(0,&[2]).unwrap();
+        todo.set_dependencies(1,&[3]).unwrap();
+        assert_eq!(todo.set_dependencies(2,&[1]),Err(DependencyError::Cycle(1)));
+        assert!(todo.list[2].depends_on.is_empty());
+    }
+
+    #[test]
+    fn one_timer_at_a_time(){
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        todo.add("Task2",1);
+        assert_eq!(todo.stop_timer(),None);
+        todo.start_timer(0);
+        todo.start_timer(0);
+        assert_eq!(todo.list[0].time_entries.len(),1);
+        assert_eq!(todo.running_timer(),Some(0));
+        todo.start_timer(1);
+        assert_eq!(todo.running_timer(),Some(1));
+        assert!(todo.list[0].time_entries[0].end.is_some());
+        assert_eq!(todo.stop_timer(),Some(1));
+        assert_eq!(todo.running_timer(),None);
+        assert!(todo.list[1].tracked(Utc::now()) >= TimeDelta::zero());
+        //Trashed tasks don't keep their timer running
+        todo.start_timer(0);
+        todo.remove(&[0]).unwrap();
+        assert_eq!(todo.running_timer(),None);
+        todo.start_timer(0);
+        todo.restore(0).unwrap();
+        assert_eq!(todo.running_timer(),Some(todo.position(2).unwrap()));
+    }
+
+    #[test]
+    fn recurring_task_is_renewed_once(){
+        let mut todo = Todo::new();
+        todo.add("Water plants", 1);
+        todo.update(0, |task| {
+            task.due = NaiveDate::from_ymd_opt(2024, 1, 31);
+            task.recurrence = Some(Recurrence::Monthly);
+        });
+        todo.done(0);
+        assert_eq!(todo.list.len(), 2);
+        let next = &todo.list[0];
+        assert!(!next.done);
+        assert_eq!(next.due, NaiveDate::from_ymd_opt(2024, 2, 29));
+        assert_eq!(next.recurrence, Some(Recurrence::Monthly));
+        todo.done(1);
+        todo.done(1);
+        assert_eq!(todo.list.len(), 2);
+    }
+
+    #[test]
+    fn history_records_changes(){
+        let mut todo = Todo::new();
+        todo.add("Task1",3);
+        todo.add("Task2",1);
+        todo.rename(todo.position(1).unwrap(),"Renamed");
+        todo.set_priority(todo.position(1).unwrap(),7);
+        todo.set_done(todo.position(1).unwrap(),true);
+        todo.set_done(todo.position(1).unwrap(),false);
+        todo.remove(&[todo.position(2).unwrap()]).unwrap();
+        //Saving records nothing more
+        todo.record_history(Utc::now());
+
+        let task = todo.task(todo.position(1).unwrap());
+        let changes:Vec<String> = task.history.iter().map(|change| change.kind.to_string()).collect();
+        assert_eq!(changes, vec!("created", "renamed from \"Task1\" to \"Renamed\"", "priority 3→7", "marked done", "marked not done"));
+        assert!(task.history.windows(2).all(|changes| changes[0].at <= changes[1].at));
+        assert_eq!(todo.trash()[0].task.history.last().map(|change| &change.kind), Some(&ChangeKind::Removed));
+    }
+
+    #[test]
+    fn add_tag_and_set_done(){
+        let mut todo = Todo::new();
+        todo.add("Task1",1);
+        todo.add_tag(0,"#backend");
+        todo.add_tag(0,"backend");
+        todo.add_tag(0," ");
+        assert_eq!(todo.list[0].tags,vec!["backend".to_string()]);
+        todo.set_done(0,true);
+        todo.set_done(0,true);
+        assert!(todo.list[0].done);
+    }
+
+    #[test]
+    fn ids_are_stable(){
+        let mut todo = Todo::new();
+        todo.add("Task1",2);
+        todo.add("Task2",5);
+        todo.sort_list();
+        assert_eq!(todo.position(1),Some(1));
+        assert_eq!(todo.position(2),Some(0));
+        todo.remove(&[0]).unwrap();
+        todo.add("Task3",1);
+        assert_eq!(todo.list[1].id,3);
+    }
+
+    #[test]
+    fn old_files_get_ids(){
+        let mut todo: Todo = serde_json::from_str(
+            r#"{"list":[{"name":"A","priority":1,"done":false},{"name":"B","priority":1,"done":true}]}"#
+        ).unwrap();
+        todo.assign_ids();
+        assert_eq!(todo.list[0].id,1);
+        assert_eq!(todo.list[1].id,2);
+        assert_eq!(todo.sort_mode(),SortMode::Priority);
+    }
+
+    #[test]
+    fn old_trashed_tasks_get_ids(){
+        let trashed = r#"{"task":{"name":"B","priority":1,"done":false},"deleted":"2024-01-01T00:00:00Z"}"#;
+        let mut todo: Todo = serde_json::from_str(
+            &format!(r#"{{"list":[{{"name":"A","priority":1,"done":false}}],"trash":[{},{}]}}"#,trashed,trashed)
+        ).unwrap();
+        todo.assign_ids();
+        assert_eq!(todo.list[0].id,1);
+        assert_eq!(todo.trash[0].task.id,2);
+        assert_eq!(todo.trash[1].task.id,3);
+        assert_eq!(todo.next_id,4);
+    }
+
     /*#[test]
     fn set_path_var(){
         let previous = Todo::load_path();
@@ -1,92 +1,171 @@
+use chrono::{Local, TimeDelta, Utc};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
     prelude::Span,
-    style::{
-        palette::tailwind::{AMBER, TEAL},
-        Color, Modifier, Style, Stylize,
-    },
+    style::{Modifier, Style, Stylize},
     symbols::{self},
     text::Line,
     widgets::{
-        Block, Borders, HighlightSpacing, List, ListItem, Padding, Paragraph,
-        StatefulWidget, Widget, Wrap,
+        Axis, Bar, BarChart, BarGroup, Block, Borders, Chart, Clear, Dataset, Gauge, GraphType,
+        HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, Sparkline, StatefulWidget,
+        Widget, Wrap,
     }
 };
 use crate::App;
-
-
-const TODO_HEADER_STYLE: Style = Style::new().fg(TEAL.c100).bg(TEAL.c800);
-const NORMAL_ROW_BG: Color = TEAL.c900;
-const ALT_ROW_BG_COLOR: Color = TEAL.c800;
-const EDIT_ROW_COLOR: Color = AMBER.c700;
-const EDIT_VALUE_COLOR: Color = AMBER.c500;
-const EDIT_STYLE: Style = Style::new().bg(EDIT_ROW_COLOR).add_modifier(Modifier::BOLD).fg(AMBER.c100);
-const EDIT_VALUE_STYLE: Style = Style::new().bg(EDIT_VALUE_COLOR).add_modifier(Modifier::BOLD).fg(AMBER.c100);
-const SELECTED_STYLE: Style = Style::new().bg(TEAL.c600).add_modifier(Modifier::BOLD);
-const TEXT_FG_COLOR: Color = TEAL.c200;
-const TEXT_STYLE: Style = Style::new().fg(TEXT_FG_COLOR);
+use crate::app::Screen;
+use crate::keymap::Mode;
+use crate::pomodoro::Phase;
+use crate::quickadd::QuickAdd;
+use crate::stats::{format_duration, Stats};
+use crate::theme::Theme;
+use crate::todo::Tier;
 
 impl App {
 
     //Renders header
-    fn render_header(area: Rect, buf: &mut Buffer) {
-        Paragraph::new("Todo List Application")
+    fn render_header(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let list = self.get_list();
+        let title = match list.running_timer() {
+            Some(index) => {
+                let task = list.task(index);
+                format!("Todo List Application - {} - {} {}", self.get_list_name(), task.name, format_clock(task.tracked(Utc::now())))
+            }
+            None => format!("Todo List Application - {}", self.get_list_name()),
+        };
+        Paragraph::new(title)
             .bold()
             .centered()
-            .bg(TEAL.c500)
+            .fg(theme.title_fg)
+            .bg(theme.title_bg)
             .render(area, buf);
     }
 
-    //Renders footer
-    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
-        let text = if self.is_edit_mode() {
-            "[Edit Mode]\nSave with Enter, Cancel with Esc\n-/+ to change priority, type to change name"
-        } else {
-            "Use ↓↑ to move, ← to unselect, → to change status\n'a' to add a task. 'Delete' to remove a task"
+    //Renders the countdown of the running pomodoro
+    fn render_pomodoro(&self, area: Rect, buf: &mut Buffer) {
+        let Some(pomodoro) = self.get_pomodoro() else {
+            return;
         };
+        let theme = self.get_theme();
+        let now = Utc::now();
+        let list = self.get_list();
+        let name = list.position(pomodoro.task_id()).map_or("", |i| list.task(i).name.as_str());
+        let (label, color) = match pomodoro.phase() {
+            Phase::Work => (format!("Focus on {}", name), theme.edit_bg),
+            Phase::Break => ("Break".to_string(), theme.selected_bg),
+        };
+        Gauge::default()
+            .gauge_style(Style::new().fg(color).bg(theme.row_bg))
+            .ratio(pomodoro.ratio(now))
+            .label(format!("{} - {} left", label, format_clock(pomodoro.remaining(now))))
+            .render(area, buf);
+    }
+
+    //Renders footer
+    fn render_footer(&self, lines: Vec<String>, area: Rect, buf: &mut Buffer) {
+        let text: Vec<Line> = lines.into_iter().map(Line::from).collect();
         Paragraph::new(text)
         .centered()
-        .bg(AMBER.c100)
-        .fg(EDIT_ROW_COLOR)
+        .bg(self.get_theme().footer_bg)
+        .fg(self.get_theme().footer_fg)
         .bold()
         .render(area, buf);
     }
 
+    //Builds the footer help from the active keymap, wrapped to the given width
+    fn footer_lines(&self, width: u16) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(message) = self.get_message() {
+            lines.push(message.clone());
+        }
+        match self.get_mode() {
+            Mode::Edit => lines.push("[Edit Mode] type to change name".to_string()),
+            Mode::Input => lines.push("Type the value".to_string()),
+            Mode::Trash => lines.push("[Trash] removed tasks are kept until the trash is emptied".to_string()),
+            Mode::Stats => lines.push("[Statistics]".to_string()),
+            Mode::Board => lines.push("[Board]".to_string()),
+            Mode::Normal | Mode::Confirm => {}
+        }
+        let mut line = String::new();
+        for entry in self.get_keymap().help(self.get_mode()) {
+            if !line.is_empty() && line.chars().count() + entry.chars().count() + 3 > width as usize {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push_str(" | ");
+            }
+            line.push_str(&entry);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
     //Renders left list
     fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let name = if self.is_ready_only() { "Ready to work" } else { "Task List" };
+        let mut title = format!("{} (by {})", name, self.get_list().sort_mode().label());
+        if let Some(filter) = self.get_filter() {
+            title.push_str(&format!(" - {}", filter.query()));
+        }
+        if let Some(user) = self.get_user().filter(|_| self.is_mine_only()) {
+            title.push_str(&format!(" - assigned to {}", user));
+        }
+        if self.marked_count() > 0 {
+            title.push_str(&format!(" - {} marked", self.marked_count()));
+        }
         let block = Block::new()
-            .title(Line::raw("Task List").centered())
+            .title(Line::raw(title).centered())
             .borders(Borders::TOP)
             .border_set(symbols::border::EMPTY)
-            .border_style(TODO_HEADER_STYLE)
-            .bg(NORMAL_ROW_BG);
+            .border_style(theme.header_style())
+            .bg(theme.row_bg);
+
+        let indexes = self.visible_indexes();
+        let grouped = self.is_grouped();
+        let group = |i: usize| self.group_key(self.get_list().task(i));
+        //Position of each task in the items, after the group headers
+        let mut positions = vec![];
+        let mut items: Vec<ListItem> = vec![];
 
         // Iterate through all elements in the `items` and stylize them.
-        let items: Vec<ListItem> = self
-            .get_list()
-            .items()
-            .iter()
-            .enumerate()
-            .map(|(i, todo_item)| {
-                let color = alternate_colors(i);
-                let displayed_name = todo_item.name.clone();
-                let mut item = ListItem::from(displayed_name).bg(color);
-                if todo_item.done {
-                    item = item.add_modifier(Modifier::CROSSED_OUT);
-                }
-                else if todo_item.priority>5 {
-                    item = item.add_modifier(Modifier::BOLD).fg(AMBER.c100);
-                }
-                item
-            })
-            .collect();
+        for (row, &i) in indexes.iter().enumerate() {
+            if grouped && (row == 0 || group(indexes[row - 1]) != group(i)) {
+                let count = indexes.iter().filter(|j| group(**j) == group(i)).count();
+                let label = self.group_label(self.get_list().task(i));
+                items.push(ListItem::from(format!("{} ({})", label, count)).style(theme.header_style()));
+            }
+            positions.push(items.len());
+            let todo_item = self.get_list().task(i);
+            let marked = self.is_marked(todo_item.id);
+            let blocked = !todo_item.done && self.get_list().is_blocked(todo_item);
+            let color = if marked { theme.marked_bg } else { theme.row_color(row) };
+            let mut displayed_name = if marked { "* ".to_string() } else { String::new() };
+            if blocked {
+                displayed_name.push_str("[blocked] ");
+            }
+            displayed_name.push_str(&todo_item.name);
+            for tag in &todo_item.tags {
+                displayed_name.push_str(&format!(" #{}", tag));
+            }
+            let item = ListItem::from(displayed_name).bg(color);
+            items.push(if todo_item.done {
+                item.add_modifier(Modifier::CROSSED_OUT)
+            } else if blocked {
+                item.style(theme.priority_style(todo_item.priority).bg(color).add_modifier(Modifier::DIM))
+            } else {
+                item.style(theme.priority_style(todo_item.priority).bg(color))
+            });
+        }
 
-        let mut selected_style = SELECTED_STYLE;
+        let mut selected_style = theme.selected_style();
         let mut symbol = " => ";
         if self.is_edit_mode() {
             symbol = "===>";
-            selected_style=EDIT_STYLE;//.add_modifier(Modifier::REVERSED);
+            selected_style = theme.edit_style();
         };
 
         let list = List::new(items)
@@ -95,70 +174,480 @@ impl App {
             .highlight_symbol(symbol)
             .highlight_spacing(HighlightSpacing::Always);
 
-        StatefulWidget::render(list, area, buf, &mut self.get_state());
+        //Headers are not selectable, the selected row is moved past them
+        let mut state = self.get_state().clone();
+        state.select(state.selected().and_then(|row| positions.get(row).copied()));
+        StatefulWidget::render(list, area, buf, &mut state);
+        *self.get_state().offset_mut() = state.offset();
     }
 
     //Renders selected task (right)
     fn render_selected_item(&self, area: Rect, buf: &mut Buffer) {
         let mut text: Vec<Line<'_>> = vec![];
-        let border_style = if self.is_edit_mode() { EDIT_STYLE } else { TODO_HEADER_STYLE };
+        let theme = self.get_theme();
+        //The new task prompt shows the task it will add instead
+        if let Some(preview) = self.get_new_task_preview() {
+            render_information(new_task_lines(&preview, theme), theme.edit_style(), theme, area, buf);
+            return;
+        }
+        let border_style = if self.is_edit_mode() { theme.edit_style() } else { theme.header_style() };
 
         match &self.get_selected() {
             Some(i) => {
                 let task = self.get_list().task(*i);
-                let style = if self.is_edit_mode() { EDIT_VALUE_STYLE } else { TEXT_STYLE };
+                let style = if self.is_edit_mode() { theme.edit_value_style() } else { theme.text_style() };
 
-                let mut name_line = vec!["Name : ".red()];
+                let mut name_line = vec!["Name : ".fg(theme.label_fg)];
 
-                let mut priority_line = vec!["Priority : ".red()];
+                let mut priority_line = vec!["Priority : ".fg(theme.label_fg)];
 
                 let state_line = vec![
-                    "Done : ".red(),
-                    Span::styled(format!("{}", task.done), TEXT_STYLE),
+                    "Done : ".fg(theme.label_fg),
+                    Span::styled(format!("{}", task.done), theme.text_style()),
+                ];
+                let workflow = self.get_workflow();
+                let workflow_line = vec![
+                    "State : ".fg(theme.label_fg),
+                    Span::styled(workflow.states()[workflow.state_of(task)].as_str(), theme.text_style()),
                 ];
 
                 if self.is_edit_mode() {
                     name_line.push(Span::styled(self.get_edit_name(), style));
                     priority_line.push(Span::styled(format!("{}", self.get_edit_priority()), style));
-                    name_line.push("_".fg(EDIT_VALUE_COLOR).add_modifier(Modifier::BOLD));
-                    priority_line.push(" (-/+)".fg(EDIT_VALUE_COLOR).bold());
+                    name_line.push("_".fg(theme.edit_value_bg).add_modifier(Modifier::BOLD));
+                    priority_line.push(" (-/+)".fg(theme.edit_value_bg).bold());
                 } else {
                     name_line.push(Span::styled(&task.name, style));
-                    priority_line.push(Span::styled(format!("{}", task.priority), TEXT_STYLE));
+                    priority_line.push(Span::styled(format!("{}", task.priority), theme.text_style()));
                 }
 
+                text.push(Line::from(vec!["Id : ".fg(theme.label_fg), Span::styled(format!("#{}", task.id), theme.text_style())]));
                 text.push(Line::from(name_line));
                 text.push(Line::from(priority_line));
                 text.push(Line::from(state_line));
+                text.push(Line::from(workflow_line));
+                if !task.depends_on.is_empty() {
+                    let list = self.get_list();
+                    let dependencies: Vec<String> = task
+                        .depends_on
+                        .iter()
+                        .map(|id| match list.position(*id) {
+                            Some(i) if list.task(i).done => format!("#{} {} (done)", id, list.task(i).name),
+                            Some(i) => format!("#{} {}", id, list.task(i).name),
+                            None => format!("#{} (removed)", id),
+                        })
+                        .collect();
+                    text.push(Line::from(vec![
+                        "Depends on : ".fg(theme.label_fg),
+                        Span::styled(dependencies.join(", "), theme.text_style()),
+                    ]));
+                    let blocked = if list.is_blocked(task) { "blocked" } else { "unblocked" };
+                    text.push(Line::from(vec![
+                        "Blocked : ".fg(theme.label_fg),
+                        Span::styled(blocked, theme.text_style()),
+                    ]));
+                }
+                if !task.tags.is_empty() {
+                    text.push(Line::from(vec![
+                        "Tags : ".fg(theme.label_fg),
+                        Span::styled(task.tags.join(", "), theme.text_style()),
+                    ]));
+                }
+                if let Some(due) = task.due {
+                    text.push(Line::from(vec![
+                        "Due : ".fg(theme.label_fg),
+                        Span::styled(due.to_string(), theme.text_style()),
+                    ]));
+                }
+                if let Some(recurrence) = task.recurrence {
+                    text.push(Line::from(vec![
+                        "Repeats : ".fg(theme.label_fg),
+                        Span::styled(recurrence.label(), theme.text_style()),
+                    ]));
+                }
+                if let Some(assignee) = &task.assignee {
+                    text.push(Line::from(vec![
+                        "Assignee : ".fg(theme.label_fg),
+                        Span::styled(assignee.as_str(), theme.text_style()),
+                    ]));
+                }
+                if let Some(at) = task.remind_at {
+                    text.push(Line::from(vec![
+                        "Reminder : ".fg(theme.label_fg),
+                        Span::styled(at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(), theme.text_style()),
+                    ]));
+                }
+                if !task.pomodoros.is_empty() {
+                    text.push(Line::from(vec![
+                        "Pomodoros : ".fg(theme.label_fg),
+                        Span::styled(format!("{}", task.pomodoros.len()), theme.text_style()),
+                    ]));
+                }
+                if !task.time_entries.is_empty() {
+                    let running = if task.running_since().is_some() { " (running)" } else { "" };
+                    text.push(Line::from(vec![
+                        "Tracked : ".fg(theme.label_fg),
+                        Span::styled(format!("{}{}", format_clock(task.tracked(Utc::now())), running), theme.text_style()),
+                    ]));
+                }
             }
             None => {
                 text.push(Line::styled("Select a task", Style::new().gray().italic()));
             }
         }
 
-        // We show the list item's info under the list in this paragraph
+        render_information(text, border_style, theme, area, buf);
+    }
+
+    //Renders the changes of the selected task, newest first (under the task information)
+    fn render_history(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let Some(i) = self.get_selected().filter(|_| area.height > 0) else {
+            return;
+        };
+        let text: Vec<Line<'_>> = self.get_list().task(i).history.iter().rev().map(|change| {
+            Line::from(vec![
+                Span::styled(format!("{} ", change.at.with_timezone(&Local).format("%m-%d %H:%M")), Style::new().fg(theme.label_fg)),
+                Span::styled(format!("{} ", change.author), theme.text_style().bold()),
+                Span::styled(change.kind.to_string(), theme.text_style()),
+            ])
+        }).collect();
+
+        let block = Block::new()
+            .title(Line::raw("History").centered())
+            .borders(Borders::all())
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme.header_style())
+            .bg(theme.row_bg)
+            .padding(Padding::horizontal(1));
+
+        Paragraph::new(text)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+    }
+
+    //Renders the removed tasks (left)
+    fn render_trash(&mut self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let block = Block::new()
+            .title(Line::raw(format!("Trash ({})", self.get_list().trash().len())).centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme.header_style())
+            .bg(theme.row_bg);
+
+        let items: Vec<ListItem> = self
+            .get_list()
+            .trash()
+            .iter()
+            .enumerate()
+            .map(|(i, trashed)| ListItem::from(trashed.task.name.clone()).bg(theme.row_color(i)))
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol(" => ")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        StatefulWidget::render(list, area, buf, self.get_trash_state());
+    }
+
+    //Renders the selected removed task (right)
+    fn render_trashed_item(&mut self, area: Rect, buf: &mut Buffer) {
+        let selected = self.get_trash_state().selected();
+        let theme = self.get_theme();
+        let text = match selected.and_then(|i| self.get_list().trash().get(i)) {
+            Some(trashed) => vec![
+                Line::from(vec!["Name : ".fg(theme.label_fg), Span::styled(&trashed.task.name, theme.text_style())]),
+                Line::from(vec!["Priority : ".fg(theme.label_fg), Span::styled(format!("{}", trashed.task.priority), theme.text_style())]),
+                Line::from(vec![
+                    "Removed : ".fg(theme.label_fg),
+                    Span::styled(trashed.deleted.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(), theme.text_style()),
+                ]),
+            ],
+            None => vec![Line::styled("Select a task to restore", Style::new().gray().italic())],
+        };
+
         let block = Block::new()
-            .title(Line::raw("Task Information").centered())
+            .title(Line::raw("Removed Task").centered())
             .borders(Borders::all())
             .border_set(symbols::border::EMPTY)
-            .border_style(border_style)
-            .bg(NORMAL_ROW_BG)
+            .border_style(theme.header_style())
+            .bg(theme.row_bg)
             .padding(Padding::horizontal(1));
 
-        // We can now render the item info
         Paragraph::new(text)
             .block(block)
             .wrap(Wrap { trim: false })
             .render(area, buf);
     }
+
+    //Renders the board, one column per workflow state
+    fn render_board(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let columns = self.board_columns();
+        let (selected_column, selected_row) = self.get_board_selection();
+        let areas = Layout::horizontal(vec![Constraint::Fill(1); columns.len()]).split(area);
+
+        for (column, (tasks, column_area)) in columns.iter().zip(areas.iter()).enumerate() {
+            let title = format!("{} ({})", self.get_workflow().states()[column], tasks.len());
+            let block = Block::new()
+                .title(Line::raw(title).centered())
+                .borders(Borders::TOP | Borders::RIGHT)
+                .border_style(theme.header_style())
+                .bg(theme.row_color(column));
+            let items: Vec<ListItem> = tasks
+                .iter()
+                .map(|i| {
+                    let task = self.get_list().task(*i);
+                    ListItem::from(task.name.clone()).style(theme.priority_style(task.priority))
+                })
+                .collect();
+            let mut state = ListState::default();
+            if column == selected_column {
+                state.select(Some(selected_row));
+            }
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(theme.selected_style())
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always);
+            StatefulWidget::render(list, *column_area, buf, &mut state);
+        }
+    }
+
+    //Renders the statistics screen in the whole main area
+    fn render_stats(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.get_theme();
+        let stats = Stats::compute(self.get_list(), Local::now().date_naive());
+        let stats_block = |title: &'static str| {
+            Block::new()
+                .title(Line::raw(title).centered())
+                .borders(Borders::TOP)
+                .border_set(symbols::border::EMPTY)
+                .border_style(theme.header_style())
+                .bg(theme.row_bg)
+                .padding(Padding::horizontal(1))
+        };
+
+        let [top_area, middle_area, bottom_area] = Layout::vertical([
+            Constraint::Length(7),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+        let [summary_area, priority_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(top_area);
+        let [daily_area, weekly_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(middle_area);
+
+        //Counts
+        let total = stats.open + stats.done;
+        let rate = (stats.done * 100).checked_div(total).unwrap_or(0);
+        let average = stats.average_time_to_done.map_or("-".to_string(), format_duration);
+        let summary: Vec<Line> = [
+            ("Open : ", stats.open.to_string()),
+            ("Done : ", stats.done.to_string()),
+            ("Completion : ", format!("{}%", rate)),
+            ("Average time to done : ", average),
+        ]
+        .into_iter()
+        .map(|(label, value)| Line::from(vec![label.fg(theme.label_fg), Span::styled(value, theme.text_style())]))
+        .collect();
+        Paragraph::new(summary)
+            .block(stats_block("Summary"))
+            .render(summary_area, buf);
+
+        //Open tasks by priority tier
+        let bars: Vec<Bar> = Tier::ALL
+            .iter()
+            .zip(stats.by_priority)
+            .map(|(tier, count)| {
+                Bar::default()
+                    .value(count as u64)
+                    .label(Line::from(tier.label()))
+                    .style(theme.tier_style(*tier))
+                    .value_style(theme.edit_value_style())
+            })
+            .collect();
+        BarChart::default()
+            .block(stats_block("Open tasks by priority"))
+            .data(BarGroup::default().bars(&bars))
+            .direction(ratatui::layout::Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .label_style(theme.text_style())
+            .render(priority_area, buf);
+
+        //Completed tasks per day and per week
+        Sparkline::default()
+            .block(stats_block("Completed per day (30 days)"))
+            .data(&stats.done_per_day)
+            .style(theme.text_style())
+            .render(daily_area, buf);
+        let weeks: Vec<Bar> = stats
+            .done_per_week
+            .iter()
+            .map(|(start, count)| {
+                Bar::default()
+                    .value(*count)
+                    .label(Line::from(start.format("%d/%m").to_string()))
+                    .style(theme.text_style())
+                    .value_style(theme.edit_value_style())
+            })
+            .collect();
+        BarChart::default()
+            .block(stats_block("Completed per week"))
+            .data(BarGroup::default().bars(&weeks))
+            .bar_width(5)
+            .label_style(theme.text_style())
+            .render(weekly_area, buf);
+
+        //Completion rate at the end of each week
+        let points: Vec<(f64, f64)> = stats
+            .completion_rate
+            .iter()
+            .enumerate()
+            .map(|(i, (_, rate))| (i as f64, *rate))
+            .collect();
+        let first_week = stats.completion_rate.first().map_or(String::new(), |(d, _)| d.format("%d/%m").to_string());
+        let last_week = stats.completion_rate.last().map_or(String::new(), |(d, _)| d.format("%d/%m").to_string());
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(theme.priority_style(10))
+            .data(&points);
+        Chart::new(vec![dataset])
+            .block(stats_block("Completion rate over time (%)"))
+            .x_axis(
+                Axis::default()
+                    .bounds([0.0, (Stats::WEEKS - 1) as f64])
+                    .labels([first_week, last_week])
+                    .style(theme.text_style()),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, 100.0])
+                    .labels(["0", "50", "100"])
+                    .style(theme.text_style()),
+            )
+            .render(bottom_area, buf);
+    }
+
+    //Renders the yes/no popup
+    fn render_confirmation(&self, area: Rect, buf: &mut Buffer) {
+        let Some(confirmation) = self.get_confirmation() else {
+            return;
+        };
+        let theme = self.get_theme();
+        let popup = centered_rect(area, 50, 4);
+        let block = Block::new()
+            .title(Line::raw("Confirm").centered())
+            .borders(Borders::all())
+            .border_style(theme.edit_style())
+            .bg(theme.row_bg);
+        Clear.render(popup, buf);
+        Paragraph::new(confirmation.question())
+            .style(theme.text_style())
+            .centered()
+            .wrap(Wrap { trim: true })
+            .block(block)
+            .render(popup, buf);
+    }
+
+    //Renders the prompt popup over the list
+    fn render_prompt(&self, area: Rect, buf: &mut Buffer) {
+        let Some(prompt) = self.get_prompt() else {
+            return;
+        };
+        let theme = self.get_theme();
+        let popup = centered_rect(area, 50, 3);
+        let block = Block::new()
+            .title(Line::raw(prompt.kind.title()).centered())
+            .borders(Borders::all())
+            .border_style(theme.edit_style())
+            .bg(theme.row_bg);
+        Clear.render(popup, buf);
+        Paragraph::new(Line::from(vec![
+            Span::styled(prompt.text.as_str(), theme.edit_value_style()),
+            "_".fg(theme.edit_value_bg).add_modifier(Modifier::BOLD),
+        ]))
+        .block(block)
+        .render(popup, buf);
+    }
+}
+
+//Describes the task the new task prompt will add
+fn new_task_lines<'a>(preview: &QuickAdd, theme: &Theme) -> Vec<Line<'a>> {
+    let style = theme.edit_value_style();
+    let mut text = vec![Line::from(vec![
+        "Name : ".fg(theme.label_fg),
+        Span::styled(preview.name.clone(), style),
+    ])];
+    let priority = preview.priority.map_or("0".to_string(), |p| p.to_string());
+    text.push(Line::from(vec!["Priority : ".fg(theme.label_fg), Span::styled(priority, style)]));
+    if !preview.tags.is_empty() {
+        text.push(Line::from(vec!["Tags : ".fg(theme.label_fg), Span::styled(preview.tags.join(", "), style)]));
+    }
+    if let Some(assignee) = &preview.assignee {
+        text.push(Line::from(vec!["Assignee : ".fg(theme.label_fg), Span::styled(assignee.clone(), style)]));
+    }
+    if let Some(due) = preview.due {
+        text.push(Line::from(vec!["Due : ".fg(theme.label_fg), Span::styled(due.to_string(), style)]));
+    }
+    if let Some(recurrence) = preview.recurrence {
+        text.push(Line::from(vec!["Repeats : ".fg(theme.label_fg), Span::styled(recurrence.label(), style)]));
+    }
+    text
+}
+
+//Renders the task information panel
+fn render_information(text: Vec<Line<'_>>, border_style: Style, theme: &Theme, area: Rect, buf: &mut Buffer) {
+    // We show the list item's info under the list in this paragraph
+    let block = Block::new()
+        .title(Line::raw("Task Information").centered())
+        .borders(Borders::all())
+        .border_set(symbols::border::EMPTY)
+        .border_style(border_style)
+        .bg(theme.row_bg)
+        .padding(Padding::horizontal(1));
+
+    // We can now render the item info
+    Paragraph::new(text)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .render(area, buf);
+}
+
+//Writes a duration like "01:05:09"
+fn format_clock(duration: TimeDelta) -> String {
+    let seconds = duration.num_seconds().max(0);
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+//Returns a rectangle centered in the area, with a width in percent and a height in lines
+fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let [_, row, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(height), Constraint::Fill(1)]).areas(area);
+    let [_, rect, _] = Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .areas(row);
+    rect
 }
 
 //Renders whole app
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let footer_length = if self.is_edit_mode() {3} else {2};
-        let [header_area, main_area, footer_area] = Layout::vertical([
+        let footer = self.footer_lines(area.width);
+        let footer_length = footer.len() as u16;
+        let pomodoro_length = if self.get_pomodoro().is_some() {1} else {0};
+        let [header_area, pomodoro_area, main_area, footer_area] = Layout::vertical([
             Constraint::Length(2),
+            Constraint::Length(pomodoro_length),
             Constraint::Fill(1),
             Constraint::Length(footer_length),
         ])
@@ -168,17 +657,28 @@ impl Widget for &mut App {
         let [list_area, item_area] =
             Layout::horizontal([Constraint::Fill(3-info_weight), Constraint::Fill(info_weight)]).areas(main_area);
 
-        App::render_header(header_area, buf);
-        self.render_footer(footer_area, buf);
-        self.render_list(list_area, buf);
-        self.render_selected_item(item_area, buf);
+        self.render_header(header_area, buf);
+        self.render_pomodoro(pomodoro_area, buf);
+        self.render_footer(footer, footer_area, buf);
+        match self.get_screen() {
+            Screen::List => {
+                self.render_list(list_area, buf);
+                let history = self.get_selected().map_or(0, |i| self.get_list().task(i).history.len());
+                let editing = self.is_edit_mode() || self.get_new_task_preview().is_some();
+                let history_length = if history == 0 || editing {0} else {(history as u16 + 2).min(item_area.height / 2)};
+                let [info_area, history_area] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(history_length)]).areas(item_area);
+                self.render_selected_item(info_area, buf);
+                self.render_history(history_area, buf);
+            }
+            Screen::Trash => {
+                self.render_trash(list_area, buf);
+                self.render_trashed_item(item_area, buf);
+            }
+            Screen::Stats => self.render_stats(main_area, buf),
+            Screen::Board => self.render_board(main_area, buf),
+        }
+        self.render_prompt(main_area, buf);
+        self.render_confirmation(main_area, buf);
     }
 }
-
-pub const fn alternate_colors(i: usize) -> Color {
-    if i % 2 == 0 {
-        NORMAL_ROW_BG
-    } else {
-        ALT_ROW_BG_COLOR
-    }
-}
\ No newline at end of file